@@ -49,7 +49,7 @@ impl futures::future::Future for Expect {
             let event = futures::try_ready!(self
                 .process
                 .poll()
-                .map_err(|e| panic!(e)));
+                .map_err(|e| panic!("{}", e)));
             match event {
                 Some(tokio_pty_process_stream::Event::Output { data }) => {
                     let s = std::string::String::from_utf8_lossy(&data);
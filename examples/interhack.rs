@@ -77,7 +77,7 @@ impl Interhack {
         let n = component_future::try_ready!(self
             .stdin
             .poll_read(&mut self.read_buf)
-            .map_err(|e| panic!(e)));
+            .map_err(|e| panic!("{}", e)));
         let input = self.filter_input(self.read_buf[..n].to_vec());
         self.process.input().send(&input);
         Ok(component_future::Async::DidWork)
@@ -87,7 +87,7 @@ impl Interhack {
         let event = component_future::try_ready!(self
             .process
             .poll()
-            .map_err(|e| panic!(e)));
+            .map_err(|e| panic!("{}", e)));
         match event {
             Some(tokio_pty_process_stream::Event::Output { data }) => {
                 let output = self.filter_output(data);
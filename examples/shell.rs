@@ -1,6 +1,5 @@
 use futures::future::Future as _;
 use futures::stream::Stream as _;
-use std::io::Write as _;
 
 mod input;
 
@@ -15,29 +14,13 @@ fn main() {
         &args,
         input::evented_stdin::Stdin::new(),
     );
-    let process = tokio_pty_process_stream::ResizingProcess::new(process);
+    let mut process = tokio_pty_process_stream::ResizingProcess::new(process);
+    process.tee(tokio::io::stdout());
 
     let _raw = crossterm::RawScreen::into_raw_mode().unwrap();
     tokio::run(
         process
-            .for_each(|ev| {
-                match ev {
-                    tokio_pty_process_stream::Event::CommandStart {
-                        ..
-                    } => {}
-                    tokio_pty_process_stream::Event::Output { data } => {
-                        let stdout = std::io::stdout();
-                        let mut stdout = stdout.lock();
-                        stdout.write_all(&data).unwrap();
-                        stdout.flush().unwrap();
-                    }
-                    tokio_pty_process_stream::Event::CommandExit {
-                        ..
-                    } => {}
-                    tokio_pty_process_stream::Event::Resize { .. } => {}
-                }
-                futures::future::ok(())
-            })
-            .map_err(|e| panic!(e)),
+            .for_each(|_| futures::future::ok(()))
+            .map_err(|e| panic!("{}", e)),
     );
 }
@@ -0,0 +1,119 @@
+use base64::Engine as _;
+
+/// Determines how requests from the process to read the clipboard
+/// (`OSC 52 ; <selection> ; ?`) are answered.
+///
+/// Allowing reads lets any program running in the process (including
+/// programs run on remote machines via `ssh`) see the contents of the
+/// clipboard, so reads are denied by default.
+#[derive(Default)]
+pub enum ClipboardReadPolicy {
+    /// Read requests are ignored.
+    #[default]
+    Deny,
+
+    /// Read requests are answered with an empty clipboard.
+    Empty,
+
+    /// Read requests are answered with the data most recently copied to the
+    /// same selection by the process itself (via `OSC 52`) while this
+    /// policy was set, or with an empty clipboard if nothing has been
+    /// copied yet.
+    Internal,
+
+    /// Read requests are answered by calling the given function with the
+    /// requested selection (for instance `"c"` for the clipboard or `"p"`
+    /// for the primary selection). If the process requests more than one
+    /// selection, the function is called for each of them in turn until one
+    /// returns data. If none do, the request is ignored.
+    Custom(Box<dyn Fn(&str) -> Option<Vec<u8>> + Send>),
+}
+
+// tracks what the process has copied, in order to support
+// ClipboardReadPolicy::Internal
+#[derive(Default)]
+pub struct Clipboard {
+    policy: ClipboardReadPolicy,
+    selections: std::collections::HashMap<String, Vec<u8>>,
+}
+
+impl Clipboard {
+    pub fn set_policy(&mut self, policy: ClipboardReadPolicy) {
+        self.policy = policy;
+        if !matches!(self.policy, ClipboardReadPolicy::Internal) {
+            self.selections.clear();
+        }
+    }
+
+    // returns each selection named by the parameter along with the data
+    // copied to it, which is empty if the data wasn't valid base64 (since
+    // that clears the selection)
+    pub fn copy(
+        &mut self,
+        param: &str,
+        data: &[u8],
+    ) -> Vec<(String, Vec<u8>)> {
+        let data = base64::engine::general_purpose::STANDARD
+            .decode(data)
+            .unwrap_or_else(|_| {
+                log::debug!("invalid clipboard data: {data:?}");
+                vec![]
+            });
+        selections(param)
+            .into_iter()
+            .map(|selection| {
+                // only keep the data around if it might be read back
+                if matches!(self.policy, ClipboardReadPolicy::Internal) {
+                    if data.is_empty() {
+                        self.selections.remove(selection);
+                    } else {
+                        self.selections
+                            .insert(selection.to_string(), data.clone());
+                    }
+                }
+                (selection.to_string(), data.clone())
+            })
+            .collect()
+    }
+
+    pub fn reply_paste(
+        &self,
+        param: &str,
+        terminator: &str,
+    ) -> Option<Vec<u8>> {
+        let data = match &self.policy {
+            ClipboardReadPolicy::Deny => return None,
+            ClipboardReadPolicy::Empty => vec![],
+            ClipboardReadPolicy::Internal => selections(param)
+                .into_iter()
+                .find_map(|selection| self.selections.get(selection))
+                .cloned()
+                .unwrap_or_default(),
+            ClipboardReadPolicy::Custom(f) => {
+                selections(param).into_iter().find_map(f)?
+            }
+        };
+        Some(
+            format!(
+                "\x1b]52;{};{}{}",
+                param,
+                base64::engine::general_purpose::STANDARD.encode(data),
+                terminator
+            )
+            .into_bytes(),
+        )
+    }
+}
+
+// the selection parameter can name several selections at once (for
+// instance "pc" for both the primary selection and the clipboard). an empty
+// parameter is passed through as is.
+fn selections(param: &str) -> Vec<&str> {
+    if param.is_empty() {
+        return vec![param];
+    }
+    param
+        .char_indices()
+        .map(|(i, c)| &param[i..i + c.len_utf8()])
+        .collect()
+}
@@ -0,0 +1,245 @@
+/// A color, as used in `Cell`.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize,
+)]
+pub enum Color {
+    /// The default terminal color.
+    Default,
+
+    /// An indexed terminal color.
+    Idx(u8),
+
+    /// An RGB terminal color.
+    Rgb(u8, u8, u8),
+}
+
+impl From<vt100::Color> for Color {
+    fn from(color: vt100::Color) -> Self {
+        match color {
+            vt100::Color::Default => Self::Default,
+            vt100::Color::Idx(i) => Self::Idx(i),
+            vt100::Color::Rgb(r, g, b) => Self::Rgb(r, g, b),
+        }
+    }
+}
+
+/// The contents and attributes of a single cell on the screen.
+#[derive(
+    Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize,
+)]
+// these mirror the attributes of vt100::Cell, which are all independent
+#[allow(clippy::struct_excessive_bools)]
+pub struct Cell {
+    /// The text contents of the cell. This will be empty for cells which
+    /// have never been written to, and for the second half of wide
+    /// characters.
+    pub contents: String,
+
+    /// The foreground color of the cell.
+    pub fgcolor: Color,
+
+    /// The background color of the cell.
+    pub bgcolor: Color,
+
+    /// Whether the cell is bold.
+    pub bold: bool,
+
+    /// Whether the cell is dim.
+    pub dim: bool,
+
+    /// Whether the cell is italic.
+    pub italic: bool,
+
+    /// Whether the cell is underlined.
+    pub underline: bool,
+
+    /// Whether the cell has inverted colors.
+    pub inverse: bool,
+
+    /// Whether the cell contains a wide character (which also occupies the
+    /// following cell).
+    pub wide: bool,
+}
+
+impl From<&vt100::Cell> for Cell {
+    fn from(cell: &vt100::Cell) -> Self {
+        Self {
+            contents: cell.contents().to_string(),
+            fgcolor: cell.fgcolor().into(),
+            bgcolor: cell.bgcolor().into(),
+            bold: cell.bold(),
+            dim: cell.dim(),
+            italic: cell.italic(),
+            underline: cell.underline(),
+            inverse: cell.inverse(),
+            wide: cell.is_wide(),
+        }
+    }
+}
+
+/// The cells which changed in a single row of the screen.
+#[derive(
+    Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize,
+)]
+pub struct RowDiff {
+    /// The index of the row on the screen.
+    pub row: u16,
+
+    /// The changed cells, as pairs of column index and new cell contents,
+    /// in increasing order of column.
+    pub cells: Vec<(u16, Cell)>,
+}
+
+/// The changes to a terminal screen between two points in time.
+///
+/// This is intended to be sent to remote clients which are rendering the
+/// screen, so that they don't need to run their own terminal emulator or
+/// receive a full copy of the screen on every update. It can be serialized
+/// with any `serde` serializer.
+#[derive(
+    Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize,
+)]
+pub struct ScreenDiff {
+    /// The size of the screen, as (rows, cols). If this differs from the
+    /// previous size, the diff contains every row of the screen.
+    pub size: (u16, u16),
+
+    /// The position of the cursor, as (row, col).
+    pub cursor_position: (u16, u16),
+
+    /// Whether the cursor is hidden.
+    pub hide_cursor: bool,
+
+    /// The rows which contain changed cells, in increasing order of row.
+    pub rows: Vec<RowDiff>,
+}
+
+impl ScreenDiff {
+    /// Computes the changes required to turn `prev` into `screen`.
+    ///
+    /// If `prev` is `None` or has a different size from `screen`, every
+    /// cell of `screen` is included.
+    #[must_use]
+    pub fn new(prev: Option<&vt100::Screen>, screen: &vt100::Screen) -> Self {
+        Self::from_snapshot(prev.map(Snapshot::new).as_ref(), screen)
+    }
+
+    pub(crate) fn from_snapshot(
+        prev: Option<&Snapshot>,
+        screen: &vt100::Screen,
+    ) -> Self {
+        let size = screen.size();
+        let prev = prev.filter(|prev| prev.size == size);
+        let (rows, cols) = size;
+
+        let mut row_diffs = vec![];
+        for row in 0..rows {
+            let mut cells = vec![];
+            for col in 0..cols {
+                let Some(cell) = screen.cell(row, col) else {
+                    continue;
+                };
+                if prev.and_then(|prev| prev.cell(row, col)) == Some(cell) {
+                    continue;
+                }
+                cells.push((col, cell.into()));
+            }
+            if !cells.is_empty() {
+                row_diffs.push(RowDiff { row, cells });
+            }
+        }
+
+        Self {
+            size,
+            cursor_position: screen.cursor_position(),
+            hide_cursor: screen.hide_cursor(),
+            rows: row_diffs,
+        }
+    }
+
+    /// Returns true if no cells changed.
+    ///
+    /// Note that the cursor position or visibility may still have changed.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+}
+
+// a copy of only the visible cells of a screen, which is all that is needed
+// to compute the next diff (cloning the whole screen would also copy the
+// scrollback)
+pub struct Snapshot {
+    size: (u16, u16),
+    cells: Vec<vt100::Cell>,
+}
+
+impl Snapshot {
+    pub fn new(screen: &vt100::Screen) -> Self {
+        let size = screen.size();
+        let (rows, cols) = size;
+        let mut cells =
+            Vec::with_capacity(usize::from(rows) * usize::from(cols));
+        for row in 0..rows {
+            for col in 0..cols {
+                if let Some(cell) = screen.cell(row, col) {
+                    cells.push(cell.clone());
+                }
+            }
+        }
+        Self { size, cells }
+    }
+
+    fn cell(&self, row: u16, col: u16) -> Option<&vt100::Cell> {
+        self.cells.get(
+            usize::from(row) * usize::from(self.size.1) + usize::from(col),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_diff() {
+        let mut parser = vt100::Parser::new(3, 10, 0);
+        let diff = ScreenDiff::new(None, parser.screen());
+        assert_eq!(diff.size, (3, 10));
+        assert_eq!(diff.rows.len(), 3);
+        assert!(diff.rows.iter().all(|row| row.cells.len() == 10));
+
+        let prev = parser.screen().clone();
+        parser.process(b"\x1b[2;3H\x1b[1;31mab\x1b[m");
+        let diff = ScreenDiff::new(Some(&prev), parser.screen());
+        assert_eq!(diff.cursor_position, (1, 4));
+        assert_eq!(diff.rows.len(), 1);
+        assert_eq!(diff.rows[0].row, 1);
+        let cols: Vec<_> = diff.rows[0].cells.iter().map(|c| c.0).collect();
+        assert_eq!(cols, vec![2, 3]);
+        let cell = &diff.rows[0].cells[0].1;
+        assert_eq!(cell.contents, "a");
+        assert_eq!(cell.fgcolor, Color::Idx(1));
+        assert!(cell.bold);
+
+        let prev = parser.screen().clone();
+        parser.process(b"\x1b[H");
+        let diff = ScreenDiff::new(Some(&prev), parser.screen());
+        assert!(diff.is_empty());
+        assert_eq!(diff.cursor_position, (0, 0));
+
+        parser.screen_mut().set_size(4, 10);
+        let diff = ScreenDiff::new(Some(&prev), parser.screen());
+        assert_eq!(diff.rows.len(), 4);
+    }
+
+    #[test]
+    fn test_serialize() {
+        let mut parser = vt100::Parser::new(2, 4, 0);
+        let prev = parser.screen().clone();
+        parser.process(b"\x1b[38;2;1;2;3mx");
+        let diff = ScreenDiff::new(Some(&prev), parser.screen());
+        let json = serde_json::to_string(&diff).unwrap();
+        assert_eq!(serde_json::from_str::<ScreenDiff>(&json).unwrap(), diff);
+    }
+}
@@ -2,6 +2,18 @@
 #[derive(Debug, snafu::Snafu)]
 #[snafu(visibility(pub))]
 pub enum Error {
+    /// failed to enable packet mode
+    #[snafu(display("failed to enable packet mode: {}", source))]
+    EnablePacketMode { source: std::io::Error },
+
+    /// failed to get terminal attributes
+    #[snafu(display("failed to get terminal attributes: {}", source))]
+    GetTermios { source: std::io::Error },
+
+    /// the process has not enabled mouse reporting
+    #[snafu(display("the process has not enabled mouse reporting"))]
+    MouseReportingDisabled,
+
     /// failed to open a pty
     #[snafu(display("failed to open a pty: {}", source))]
     OpenPty { source: std::io::Error },
@@ -10,6 +22,10 @@ pub enum Error {
     #[snafu(display("failed to poll for process exit: {}", source))]
     ProcessExitPoll { source: std::io::Error },
 
+    /// the pty has not been opened yet
+    #[snafu(display("the pty has not been opened yet"))]
+    PtyNotOpen,
+
     /// failed to read from pty
     #[snafu(display("failed to read from pty: {}", source))]
     ReadPty { source: std::io::Error },
@@ -27,11 +43,51 @@ pub enum Error {
         source: tokio_terminal_resize::Error,
     },
 
+    /// failed to poll for terminal sizes
+    #[snafu(display("failed to poll for terminal sizes: {}", source))]
+    ResizeSource {
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+
+    /// failed to set terminal attributes
+    #[snafu(display("failed to set terminal attributes: {}", source))]
+    SetTermios { source: std::io::Error },
+
+    /// failed to install shell integration scripts
+    #[snafu(display(
+        "failed to install shell integration scripts: {}",
+        source
+    ))]
+    ShellIntegration { source: std::io::Error },
+
+    /// subscriber was disconnected for falling too far behind
+    #[snafu(display(
+        "subscriber was disconnected for falling too far behind"
+    ))]
+    SubscriberLagged,
+
+    /// failed to receive event from subscription
+    #[snafu(display(
+        "failed to receive event from subscription: {}",
+        source
+    ))]
+    Subscription {
+        source: tokio::sync::mpsc::error::RecvError,
+    },
+
     /// failed to spawn process
     #[snafu(display("failed to spawn process for `{}`: {}", cmd, source))]
     SpawnProcess { cmd: String, source: std::io::Error },
 
+    /// failed to wait for a timer
+    #[snafu(display("failed to wait for a timer: {}", source))]
+    Timer { source: tokio::timer::Error },
+
     /// failed to write to pty
     #[snafu(display("failed to write to pty: {}", source))]
     WritePty { source: std::io::Error },
+
+    /// failed to write to tee sink
+    #[snafu(display("failed to write to tee sink: {}", source))]
+    WriteTee { source: std::io::Error },
 }
@@ -0,0 +1,473 @@
+use std::fmt::Write as _;
+
+/// Options controlling how `screen_to_html` and `transcript_to_html` render
+/// a terminal screen.
+pub struct HtmlOptions {
+    /// The colors to use for the default foreground and background colors
+    /// and the indexed color palette.
+    pub colors: crate::query::TerminalProfile,
+
+    /// Whether to include the scrollback above the visible screen.
+    pub include_scrollback: bool,
+
+    /// The title of the generated page.
+    pub title: String,
+}
+
+impl Default for HtmlOptions {
+    fn default() -> Self {
+        Self {
+            colors: crate::query::TerminalProfile::default(),
+            include_scrollback: false,
+            title: "terminal".to_string(),
+        }
+    }
+}
+
+/// Renders a terminal screen as a standalone HTML page.
+///
+/// Colors (including 256 color and truecolor) and the bold, dim, italic,
+/// underline, and inverse attributes are preserved, using inline styles so
+/// that the page can be viewed on its own. The screen doesn't record which
+/// text was covered by a hyperlink, so use `transcript_to_html` to include
+/// links.
+#[must_use]
+pub fn screen_to_html(
+    screen: &vt100::Screen,
+    options: &HtmlOptions,
+) -> String {
+    render(screen, options, &Links::new())
+}
+
+/// Renders the output retained in a transcript as a standalone HTML page.
+///
+/// The transcript is replayed into a fresh terminal of the given size
+/// (`(rows, cols)`) with unlimited scrollback, and the entire resulting
+/// history is rendered as with `screen_to_html`. Text which was printed as
+/// part of a hyperlink (`OSC 8`) is rendered as a link, as long as it is
+/// still on the screen and the link uses the `http`, `https`, `file`, or
+/// `mailto` scheme (other links are rendered as plain text).
+#[must_use]
+pub fn transcript_to_html(
+    transcript: &crate::transcript::Transcript,
+    size: (u16, u16),
+    options: &HtmlOptions,
+) -> String {
+    let contents = transcript.contents();
+    let mut parser = vt100::Parser::new(size.0, size.1, usize::MAX);
+
+    let mut extractor = crate::hyperlink::HyperlinkExtractor::new();
+    let mut hyperlinks = extractor.process(&contents);
+    hyperlinks.extend(extractor.flush());
+
+    let mut links = LinkCells::default();
+    let mut offset = 0;
+    for link in &hyperlinks {
+        if link.offset < offset || !allowed_uri(&link.uri) {
+            continue;
+        }
+        parser.process(&contents[offset..link.offset]);
+        offset = link.offset
+            + links.record(&mut parser, link, &contents[link.offset..]);
+    }
+    parser.process(&contents[offset..]);
+
+    let screen = parser.screen();
+    render(
+        screen,
+        &HtmlOptions {
+            colors: options.colors.clone(),
+            include_scrollback: true,
+            title: options.title.clone(),
+        },
+        &links.resolve(screen),
+    )
+}
+
+// only links which can't run script when clicked (unlike javascript: or
+// data: uris) are rendered
+fn allowed_uri(uri: &str) -> bool {
+    let Some((scheme, _)) = uri.split_once(':') else {
+        return false;
+    };
+    ["http", "https", "file", "mailto"]
+        .iter()
+        .any(|allowed| scheme.eq_ignore_ascii_case(allowed))
+}
+
+// maps the position of each cell covered by a link (as a row index as
+// described in Position::row, and a column) to the uri of the link and the
+// contents the cell had when the link was printed
+type Links = std::collections::HashMap<(i32, u16), (String, String)>;
+
+// the cells which were printed while a link was active, recorded while
+// replaying the output into a terminal
+#[derive(Default)]
+struct LinkCells {
+    // each cell is identified by whether it was on the alternate screen and
+    // its row counted from the start of the scrollback
+    cells: std::collections::HashMap<(bool, usize, u16), (String, String)>,
+}
+
+impl LinkCells {
+    // feeds the text covered by link (which starts at the beginning of data)
+    // into the parser one byte at a time, recording the cell which each
+    // printed character ends up in. returns the number of bytes processed.
+    fn record(
+        &mut self,
+        parser: &mut vt100::Parser,
+        link: &crate::hyperlink::Hyperlink,
+        data: &[u8],
+    ) -> usize {
+        let mut remaining = link
+            .text
+            .chars()
+            .filter(|&c| c != '\n' && c != '\t')
+            .count();
+        let mut prints = PrintCounter::default();
+        let mut vte = vte::Parser::new();
+        let mut len = 0;
+        while remaining > 0 && len < data.len() {
+            let byte = &data[len..=len];
+            len += 1;
+            parser.process(byte);
+            vte.advance(&mut prints, byte);
+            for _ in 0..std::mem::take(&mut prints.count) {
+                remaining = remaining.saturating_sub(1);
+                self.record_cell(parser, &link.uri);
+            }
+        }
+        len
+    }
+
+    // the character which was just printed is in the cell before the
+    // cursor (the cursor column is one past the last column when a wrap is
+    // pending), or two cells before it for wide characters
+    fn record_cell(&mut self, parser: &mut vt100::Parser, uri: &str) {
+        let screen = parser.screen_mut();
+        screen.set_scrollback(usize::MAX);
+        let scrollback = screen.scrollback();
+        screen.set_scrollback(0);
+
+        let (row, col) = screen.cursor_position();
+        let Some(mut col) = col.checked_sub(1) else {
+            return;
+        };
+        if col > 0
+            && screen
+                .cell(row, col)
+                .is_some_and(vt100::Cell::is_wide_continuation)
+        {
+            col -= 1;
+        }
+        let Some(cell) = screen.cell(row, col) else {
+            return;
+        };
+        let alternate = screen.alternate_screen();
+        let row = usize::from(row) + if alternate { 0 } else { scrollback };
+        self.cells.insert(
+            (alternate, row, col),
+            (uri.to_string(), cell.contents().to_string()),
+        );
+    }
+
+    // converts the recorded cells to positions on the final screen
+    fn resolve(self, screen: &vt100::Screen) -> Links {
+        let mut screen = screen.clone();
+        screen.set_scrollback(usize::MAX);
+        let scrollback = screen.scrollback();
+        let alternate = screen.alternate_screen();
+        self.cells
+            .into_iter()
+            .filter(|((alt, _, _), _)| *alt == alternate)
+            .map(|((_, row, col), link)| {
+                // the scrollback will never be anywhere near i32::MAX rows
+                #[allow(
+                    clippy::cast_possible_truncation,
+                    clippy::cast_possible_wrap
+                )]
+                let row = if alternate {
+                    row as i32
+                } else {
+                    row as i32 - scrollback as i32
+                };
+                ((row, col), link)
+            })
+            .collect()
+    }
+}
+
+#[derive(Default)]
+struct PrintCounter {
+    count: usize,
+}
+
+impl vte::Perform for PrintCounter {
+    fn print(&mut self, _: char) {
+        self.count += 1;
+    }
+}
+
+fn render(
+    screen: &vt100::Screen,
+    options: &HtmlOptions,
+    links: &Links,
+) -> String {
+    let mut body = String::new();
+    crate::search::for_each_row(
+        screen,
+        options.include_scrollback,
+        |screen, row, index| {
+            render_row(&mut body, screen, row, index, links, options);
+            body.push('\n');
+        },
+    );
+
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE html>\n<html>\n<head>\n");
+    html.push_str("<meta charset=\"utf-8\">\n");
+    let _ = writeln!(html, "<title>{}</title>", escape(&options.title));
+    let _ = writeln!(
+        html,
+        "<style>body {{ margin: 0; }} pre {{ margin: 0; padding: 0.5em; \
+         font-family: monospace; color: {}; background-color: {}; }} \
+         a {{ color: inherit; }}</style>",
+        css_color(options.colors.foreground),
+        css_color(options.colors.background),
+    );
+    html.push_str("</head>\n<body>\n<pre>");
+    html.push_str(&body);
+    html.push_str("</pre>\n</body>\n</html>\n");
+    html
+}
+
+// the resolved appearance of a cell, shared with the svg exporter
+#[derive(Clone, PartialEq, Eq)]
+// each attribute can be set independently of the others
+#[allow(clippy::struct_excessive_bools)]
+pub struct Style<'a> {
+    pub fg: (u8, u8, u8),
+    pub bg: Option<(u8, u8, u8)>,
+    pub bold: bool,
+    pub dim: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub link: Option<&'a str>,
+}
+
+impl Style<'_> {
+    fn css(&self, default_fg: (u8, u8, u8)) -> String {
+        let mut css = vec![];
+        if self.fg != default_fg {
+            css.push(format!("color: {}", css_color(self.fg)));
+        }
+        if let Some(bg) = self.bg {
+            css.push(format!("background-color: {}", css_color(bg)));
+        }
+        if self.bold {
+            css.push("font-weight: bold".to_string());
+        }
+        if self.dim {
+            css.push("opacity: 0.5".to_string());
+        }
+        if self.italic {
+            css.push("font-style: italic".to_string());
+        }
+        if self.underline {
+            css.push("text-decoration: underline".to_string());
+        }
+        css.join("; ")
+    }
+}
+
+fn render_row(
+    out: &mut String,
+    screen: &vt100::Screen,
+    row: u16,
+    index: i32,
+    links: &Links,
+    options: &HtmlOptions,
+) {
+    let (_, cols) = screen.size();
+    let mut spans: Vec<(Style, String)> = vec![];
+    for col in 0..cols {
+        let Some(cell) = screen.cell(row, col) else {
+            continue;
+        };
+        if cell.is_wide_continuation() {
+            continue;
+        }
+        // the cell may have been overwritten since the link was printed
+        let link = links
+            .get(&(index, col))
+            .filter(|(_, contents)| contents == cell.contents())
+            .map(|(uri, _)| uri.as_str());
+        let style = cell_style(cell, link, &options.colors);
+        let contents = if cell.has_contents() {
+            cell.contents()
+        } else {
+            " "
+        };
+        match spans.last_mut() {
+            Some((last, text)) if *last == style => text.push_str(contents),
+            _ => spans.push((style, contents.to_string())),
+        }
+    }
+
+    // trailing blank space would just make the page wider
+    if let Some((style, text)) = spans.last_mut() {
+        if style.bg.is_none() && style.link.is_none() {
+            let len = text.trim_end().len();
+            text.truncate(len);
+        }
+    }
+
+    for (style, text) in spans {
+        if text.is_empty() {
+            continue;
+        }
+        if let Some(uri) = style.link {
+            let _ = write!(out, "<a href=\"{}\">", escape(uri));
+        }
+        let css = style.css(options.colors.foreground);
+        if css.is_empty() {
+            out.push_str(&escape(&text));
+        } else {
+            let _ = write!(
+                out,
+                "<span style=\"{}\">{}</span>",
+                css,
+                escape(&text)
+            );
+        }
+        if style.link.is_some() {
+            out.push_str("</a>");
+        }
+    }
+}
+
+pub fn cell_style<'a>(
+    cell: &vt100::Cell,
+    link: Option<&'a str>,
+    colors: &crate::query::TerminalProfile,
+) -> Style<'a> {
+    let mut fg =
+        resolve_color(cell.fgcolor(), colors).unwrap_or(colors.foreground);
+    let mut bg = resolve_color(cell.bgcolor(), colors);
+    if cell.inverse() {
+        let new_bg = fg;
+        fg = bg.unwrap_or(colors.background);
+        bg = Some(new_bg);
+    }
+    Style {
+        fg,
+        bg,
+        bold: cell.bold(),
+        dim: cell.dim(),
+        italic: cell.italic(),
+        underline: cell.underline(),
+        link,
+    }
+}
+
+// returns None for the default color
+fn resolve_color(
+    color: vt100::Color,
+    colors: &crate::query::TerminalProfile,
+) -> Option<(u8, u8, u8)> {
+    match color {
+        vt100::Color::Default => None,
+        vt100::Color::Idx(i) => colors.palette.get(usize::from(i)).copied(),
+        vt100::Color::Rgb(r, g, b) => Some((r, g, b)),
+    }
+}
+
+pub fn css_color((r, g, b): (u8, u8, u8)) -> String {
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
+
+pub fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_screen_to_html() {
+        let mut parser = vt100::Parser::new(3, 20, 0);
+        parser.process(
+            b"\x1b[1;31mred\x1b[m \x1b[38;5;196;3mx\x1b[m \
+              \x1b[38;2;1;2;3;4my\x1b[m \x1b[7mz\x1b[m <&>\r\n\
+              \x1b]8;;https://example.com\x1b\\link\x1b]8;;\x1b\\",
+        );
+        let html = screen_to_html(parser.screen(), &HtmlOptions::default());
+
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        let body = &html
+            [html.find("<pre>").unwrap() + 5..html.find("</pre>").unwrap()];
+        assert_eq!(
+            body,
+            "<span style=\"color: #cd0000; font-weight: bold\">red</span> \
+             <span style=\"color: #ff0000; font-style: italic\">x</span> \
+             <span style=\"color: #010203; text-decoration: underline\">y\
+             </span> \
+             <span style=\"color: #000000; background-color: #e5e5e5\">z\
+             </span> &lt;&amp;&gt;\n\
+             link\n\n"
+        );
+    }
+
+    #[test]
+    fn test_transcript_to_html() {
+        let mut transcript = crate::transcript::Transcript::new(
+            crate::transcript::TranscriptLimit::Lines(100),
+        );
+        transcript.push(b"one\r\ntwo\r\nthree\r\n");
+        let html =
+            transcript_to_html(&transcript, (2, 10), &HtmlOptions::default());
+        assert!(html.contains("<pre>one\ntwo\nthree\n\n</pre>"), "{}", html);
+    }
+
+    #[test]
+    fn test_transcript_links() {
+        let mut transcript = crate::transcript::Transcript::new(
+            crate::transcript::TranscriptLimit::Lines(100),
+        );
+        transcript.push(
+            b"one \x1b]8;;https://example.com\x1b\\li\x1b[1mn\x1b[mk\x1b]8;;\x1b\\\r\n\
+              \x1b]8;;javascript:alert(1)\x07bad\x1b]8;;\x07\r\n\
+              see \x1b]8;;file:///tmp/x\x07wrapped!!\x1b]8;;\x07\r\n\
+              link\r\n\
+              \x1b]8;;mailto:a@b\x07ab\x1b]8;;\x07\rx\r\n",
+        );
+        let html =
+            transcript_to_html(&transcript, (2, 10), &HtmlOptions::default());
+        let body = &html
+            [html.find("<pre>").unwrap() + 5..html.find("</pre>").unwrap()];
+        assert_eq!(
+            body,
+            "one <a href=\"https://example.com\">li</a>\
+             <a href=\"https://example.com\">\
+             <span style=\"font-weight: bold\">n</span></a>\
+             <a href=\"https://example.com\">k</a>\n\
+             bad\n\
+             see <a href=\"file:///tmp/x\">wrappe</a>\n\
+             <a href=\"file:///tmp/x\">d!!</a>\n\
+             link\n\
+             x<a href=\"mailto:a@b\">b</a>\n\
+             \n"
+        );
+    }
+}
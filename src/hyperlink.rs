@@ -0,0 +1,241 @@
+/// A hyperlink emitted by the process, along with the text it covers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hyperlink {
+    /// The target of the link.
+    pub uri: String,
+
+    /// The `id` parameter of the link, if one was given. Programs use this
+    /// to indicate that separate spans of text (for instance, a link which
+    /// is split across multiple lines by a text editor) refer to the same
+    /// link.
+    pub id: Option<String>,
+
+    /// The text which was printed while the link was active. Newlines and
+    /// tabs are included, but other control characters and escape sequences
+    /// are removed.
+    pub text: String,
+
+    /// The offset in the output processed by the extractor at which the
+    /// link starts (just after the sequence which opened it). If the
+    /// extractor is fed all of the data from each `Event::Output`, this is
+    /// the offset within the output of the process.
+    pub offset: usize,
+}
+
+/// A streaming parser which extracts hyperlinks (`OSC 8`) from terminal
+/// output.
+///
+/// Programs like `ls --hyperlink`, `gcc`, and `cargo` mark text as a link by
+/// printing `OSC 8 ; params ; URI ST` before the text and `OSC 8 ; ; ST`
+/// after it. This parser tracks these sequences and returns each link along
+/// with the text it covers once the link is closed. Like `AnsiStripper`, the
+/// parser is stateful, so this can be fed the data from each `Event::Output`
+/// directly.
+///
+/// A `Process` with terminal state tracking enabled already reports links
+/// as `Event::Hyperlink` events, so this is mainly useful for output which
+/// isn't coming from a `Process` (such as a saved transcript), or when
+/// terminal state tracking isn't otherwise needed.
+pub struct HyperlinkExtractor {
+    parser: vte::Parser,
+    performer: Performer,
+    offset: usize,
+}
+
+impl HyperlinkExtractor {
+    /// Creates a new extractor.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            parser: vte::Parser::new(),
+            performer: Performer {
+                current: None,
+                links: vec![],
+                opened: false,
+                string_terminated: false,
+            },
+            offset: 0,
+        }
+    }
+
+    /// Processes a chunk of terminal output, returning any links which were
+    /// closed.
+    pub fn process(&mut self, data: &[u8]) -> Vec<Hyperlink> {
+        // the rest of an ST which was split across chunks
+        let mut i = if std::mem::take(&mut self.performer.string_terminated)
+            && data.first() == Some(&b'\\')
+        {
+            self.parser.advance(&mut self.performer, &data[..1]);
+            if let Some(link) = &mut self.performer.current {
+                link.offset += 1;
+            }
+            1
+        } else {
+            0
+        };
+
+        // stop after each link is opened, in order to record its offset
+        while i < data.len() {
+            i += self
+                .parser
+                .advance_until_terminated(&mut self.performer, &data[i..]);
+            if !std::mem::take(&mut self.performer.opened) {
+                continue;
+            }
+            // vte dispatches an OSC as soon as it sees the ESC at the start
+            // of ST, so skip over the rest of the terminator too
+            if self.performer.string_terminated && i < data.len() {
+                self.performer.string_terminated = false;
+                if data[i] == b'\\' {
+                    self.parser.advance(&mut self.performer, &data[i..=i]);
+                    i += 1;
+                }
+            }
+            if let Some(link) = &mut self.performer.current {
+                link.offset = self.offset + i;
+            }
+        }
+        self.offset += data.len();
+
+        std::mem::take(&mut self.performer.links)
+    }
+
+    /// Returns the link which is currently open, if any, along with the text
+    /// it has covered so far.
+    ///
+    /// This is useful once the process has exited, since programs may not
+    /// close the last link they print. The link is removed, so subsequent
+    /// text will not be included in any link until a new one is started.
+    pub fn flush(&mut self) -> Option<Hyperlink> {
+        self.performer.current.take()
+    }
+}
+
+impl Default for HyperlinkExtractor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+struct Performer {
+    current: Option<Hyperlink>,
+    links: Vec<Hyperlink>,
+    opened: bool,
+    string_terminated: bool,
+}
+
+impl Performer {
+    fn close(&mut self) {
+        if let Some(link) = self.current.take() {
+            self.links.push(link);
+        }
+    }
+}
+
+impl vte::Perform for Performer {
+    fn print(&mut self, c: char) {
+        if let Some(link) = &mut self.current {
+            link.text.push(c);
+        }
+    }
+
+    fn execute(&mut self, byte: u8) {
+        if let Some(link) = &mut self.current {
+            if byte == b'\n' || byte == b'\t' {
+                link.text.push(char::from(byte));
+            }
+        }
+    }
+
+    fn osc_dispatch(&mut self, params: &[&[u8]], bell_terminated: bool) {
+        let [b"8", link_params, uri @ ..] = params else {
+            return;
+        };
+
+        // starting a new link implicitly closes the previous one
+        self.close();
+
+        // uris can contain semicolons, which the parser will have split
+        let uri = uri
+            .iter()
+            .map(|part| String::from_utf8_lossy(part))
+            .collect::<Vec<_>>()
+            .join(";");
+        if uri.is_empty() {
+            return;
+        }
+        let id = link_params
+            .split(|&c| c == b':')
+            .find_map(|param| param.strip_prefix(b"id="))
+            .map(|id| String::from_utf8_lossy(id).into_owned());
+        self.current = Some(Hyperlink {
+            uri,
+            id,
+            text: String::new(),
+            offset: 0,
+        });
+        self.opened = true;
+        self.string_terminated = !bell_terminated;
+    }
+
+    fn terminated(&self) -> bool {
+        self.opened
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_extract() {
+        let mut extractor = HyperlinkExtractor::new();
+        assert_eq!(
+            extractor.process(
+                b"see \x1b]8;;https://example.com/a;b\x1b\\\x1b[1mthe\x1b[m"
+            ),
+            vec![]
+        );
+        assert_eq!(
+            extractor.process(b" docs\x1b]8;;\x1b\\ or \x1b]8;foo=1:id=x;f"),
+            vec![Hyperlink {
+                uri: "https://example.com/a;b".to_string(),
+                id: None,
+                text: "the docs".to_string(),
+                offset: 34,
+            }]
+        );
+        assert_eq!(
+            extractor.process(b"ile:///tmp\x07a\r\nb\x1b]8;;file:///c\x07c"),
+            vec![Hyperlink {
+                uri: "file:///tmp".to_string(),
+                id: Some("x".to_string()),
+                text: "a\nb".to_string(),
+                offset: 87,
+            }]
+        );
+        assert_eq!(
+            extractor.flush(),
+            Some(Hyperlink {
+                uri: "file:///c".to_string(),
+                id: None,
+                text: "c".to_string(),
+                offset: 106,
+            })
+        );
+        assert_eq!(extractor.flush(), None);
+
+        // an ST split across chunks
+        let mut extractor = HyperlinkExtractor::new();
+        assert_eq!(extractor.process(b"ab\x1b]8;;x\x1b"), vec![]);
+        assert_eq!(
+            extractor.process(b"\\y\x1b]8;;\x07"),
+            vec![Hyperlink {
+                uri: "x".to_string(),
+                id: None,
+                text: "y".to_string(),
+                offset: 10,
+            }]
+        );
+    }
+}
@@ -50,11 +50,11 @@
 //!                 tokio_pty_process_stream::Event::CommandExit {
 //!                     ..
 //!                 } => {}
-//!                 tokio_pty_process_stream::Event::Resize { .. } => {}
+//!                 _ => {}
 //!             }
 //!             futures::future::ok(())
 //!         })
-//!         .map_err(|e| panic!(e)),
+//!         .map_err(|e| panic!("{}", e)),
 //! );
 //! #
 //! # struct EventedStdin;
@@ -148,10 +148,44 @@
 #![allow(clippy::multiple_crate_versions)]
 #![allow(clippy::type_complexity)]
 
+mod clipboard;
+pub use clipboard::ClipboardReadPolicy;
+mod diff;
+pub use diff::{Cell, Color, RowDiff, ScreenDiff};
 mod error;
 pub use error::Error;
+mod html;
+pub use html::{screen_to_html, transcript_to_html, HtmlOptions};
+mod hyperlink;
+pub use hyperlink::{Hyperlink, HyperlinkExtractor};
+mod mode;
+pub use mode::Mode;
+mod mouse;
+pub use mouse::{MouseButton, MouseEncoding, MouseEvent, MouseEventKind};
+mod packet;
+pub use packet::FlowControl;
 mod process;
 pub use process::Event;
 pub use process::Process;
+mod query;
+pub use query::TerminalProfile;
 mod resize;
 pub use resize::ResizingProcess;
+mod screen;
+pub use screen::ScreenProcess;
+mod search;
+pub use search::{Match, Position, ScreenText};
+mod shell;
+mod strip;
+pub use strip::{AnsiStripper, StripOptions};
+mod subscribe;
+pub use subscribe::{LagPolicy, SubscribeOptions, Subscription};
+mod svg;
+pub use svg::{SvgOptions, SvgRecorder};
+mod term;
+mod termios;
+pub use termios::{SpecialChar, Termios, TermiosConfig};
+mod timed;
+pub use timed::{TimedEvent, TimedProcess, TimedStream};
+mod transcript;
+pub use transcript::{Transcript, TranscriptLimit};
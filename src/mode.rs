@@ -0,0 +1,51 @@
+/// Terminal modes which can be enabled or disabled by the process.
+///
+/// See `Event::ModeChanged` and `Process::mode`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Mode {
+    /// The process is drawing to the alternate screen (`CSI ? 47 h` or
+    /// `CSI ? 1049 h`), as full screen programs like editors and pagers
+    /// typically do.
+    AlternateScreen,
+
+    /// The process has requested mouse events to be reported, in any of the
+    /// mouse reporting modes (`CSI ? 9 h`, `CSI ? 1000 h`, `CSI ? 1002 h`,
+    /// or `CSI ? 1003 h`).
+    MouseReporting,
+
+    /// Pasted text should be surrounded by `CSI 200 ~` and `CSI 201 ~`
+    /// (`CSI ? 2004 h`).
+    BracketedPaste,
+
+    /// The process has requested to be notified when the terminal gains or
+    /// loses focus (`CSI ? 1004 h`).
+    FocusReporting,
+
+    /// The cursor keys should send application sequences (`ESC O A`) rather
+    /// than normal sequences (`CSI A`) (`CSI ? 1 h`).
+    ApplicationCursor,
+}
+
+impl Mode {
+    pub(crate) const ALL: [Self; 5] = [
+        Self::AlternateScreen,
+        Self::MouseReporting,
+        Self::BracketedPaste,
+        Self::FocusReporting,
+        Self::ApplicationCursor,
+    ];
+
+    // the mode controlled by the given DECSET/DECRST (CSI ? Pm h/l)
+    // parameter, if it is one we report
+    pub(crate) fn from_decset(param: u16) -> Option<Self> {
+        match param {
+            47 | 1047 | 1049 => Some(Self::AlternateScreen),
+            9 | 1000 | 1002 | 1003 => Some(Self::MouseReporting),
+            2004 => Some(Self::BracketedPaste),
+            1004 => Some(Self::FocusReporting),
+            1 => Some(Self::ApplicationCursor),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,251 @@
+use std::convert::TryFrom as _;
+
+/// A mouse button, as used in `MouseEvent`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MouseButton {
+    /// The left mouse button.
+    Left,
+
+    /// The middle mouse button.
+    Middle,
+
+    /// The right mouse button.
+    Right,
+
+    /// Scrolling the mouse wheel up.
+    WheelUp,
+
+    /// Scrolling the mouse wheel down.
+    WheelDown,
+}
+
+/// The type of action in a `MouseEvent`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MouseEventKind {
+    /// A mouse button was pressed.
+    Press(MouseButton),
+
+    /// A mouse button was released.
+    Release(MouseButton),
+
+    /// The mouse was moved while a button was held down.
+    Drag(MouseButton),
+
+    /// The mouse was moved with no buttons held down.
+    Move,
+}
+
+/// A mouse event to send to the process via `Process::send_mouse`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MouseEvent {
+    /// The action which happened.
+    pub kind: MouseEventKind,
+
+    /// The (0-based) row of the screen where the event happened.
+    pub row: u16,
+
+    /// The (0-based) column of the screen where the event happened.
+    pub col: u16,
+
+    /// Whether the shift key was held down.
+    pub shift: bool,
+
+    /// Whether the alt (meta) key was held down.
+    pub alt: bool,
+
+    /// Whether the control key was held down.
+    pub ctrl: bool,
+}
+
+impl MouseEvent {
+    /// Creates a new `MouseEvent` at the given location with no modifier
+    /// keys held down.
+    #[must_use]
+    pub fn new(kind: MouseEventKind, row: u16, col: u16) -> Self {
+        Self {
+            kind,
+            row,
+            col,
+            shift: false,
+            alt: false,
+            ctrl: false,
+        }
+    }
+}
+
+/// The encoding used to report mouse events to the process.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MouseEncoding {
+    /// The original encoding, where each value is sent as a single byte.
+    /// Coordinates past column or row 223 can't be reported.
+    Default,
+
+    /// Like `Default`, but with values encoded as UTF-8 characters
+    /// (`CSI ? 1005 h`).
+    Utf8,
+
+    /// `CSI < b ; x ; y M` (`CSI ? 1006 h`).
+    Sgr,
+
+    /// `CSI b ; x ; y M` (`CSI ? 1015 h`).
+    Urxvt,
+}
+
+// returns None if the event isn't reported in the given mode, or can't be
+// represented in the given encoding
+pub fn encode(
+    event: MouseEvent,
+    mode: vt100::MouseProtocolMode,
+    encoding: MouseEncoding,
+) -> Option<Vec<u8>> {
+    let (button, release, motion) = match event.kind {
+        MouseEventKind::Press(button) => (Some(button), false, false),
+        MouseEventKind::Release(button) => (Some(button), true, false),
+        MouseEventKind::Drag(button) => (Some(button), false, true),
+        MouseEventKind::Move => (None, false, true),
+    };
+
+    let reported = match mode {
+        vt100::MouseProtocolMode::None => false,
+        vt100::MouseProtocolMode::Press => !release && !motion,
+        vt100::MouseProtocolMode::PressRelease => !motion,
+        vt100::MouseProtocolMode::ButtonMotion => button.is_some(),
+        vt100::MouseProtocolMode::AnyMotion => true,
+    };
+    // the wheel buttons have no release events
+    let is_wheel =
+        matches!(button, Some(MouseButton::WheelUp | MouseButton::WheelDown));
+    if !reported || (release && is_wheel) {
+        return None;
+    }
+
+    // only the sgr encoding can report which button was released
+    let mut code: u16 = if release && encoding != MouseEncoding::Sgr {
+        3
+    } else {
+        match button {
+            Some(MouseButton::Left) => 0,
+            Some(MouseButton::Middle) => 1,
+            Some(MouseButton::Right) => 2,
+            Some(MouseButton::WheelUp) => 64,
+            Some(MouseButton::WheelDown) => 65,
+            None => 3,
+        }
+    };
+    if motion {
+        code += 32;
+    }
+    // x10 mode doesn't report modifiers
+    if mode != vt100::MouseProtocolMode::Press {
+        if event.shift {
+            code += 4;
+        }
+        if event.alt {
+            code += 8;
+        }
+        if event.ctrl {
+            code += 16;
+        }
+    }
+
+    let x = u32::from(event.col) + 1;
+    let y = u32::from(event.row) + 1;
+    match encoding {
+        MouseEncoding::Default => {
+            let mut bytes = b"\x1b[M".to_vec();
+            for value in &[u32::from(code), x, y] {
+                bytes.push(u8::try_from(value + 32).ok()?);
+            }
+            Some(bytes)
+        }
+        MouseEncoding::Utf8 => {
+            let mut s = "\x1b[M".to_string();
+            for value in &[u32::from(code), x, y] {
+                // xterm doesn't report coordinates which would need more
+                // than two bytes to encode
+                if value + 32 >= 0x800 {
+                    return None;
+                }
+                s.push(char::from_u32(value + 32)?);
+            }
+            Some(s.into_bytes())
+        }
+        MouseEncoding::Sgr => Some(
+            format!(
+                "\x1b[<{};{};{}{}",
+                code,
+                x,
+                y,
+                if release { 'm' } else { 'M' }
+            )
+            .into_bytes(),
+        ),
+        MouseEncoding::Urxvt => {
+            Some(format!("\x1b[{};{};{}M", code + 32, x, y).into_bytes())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_encode() {
+        let press =
+            MouseEvent::new(MouseEventKind::Press(MouseButton::Left), 2, 4);
+        let release = MouseEvent::new(
+            MouseEventKind::Release(MouseButton::Right),
+            2,
+            4,
+        );
+        let drag = MouseEvent {
+            ctrl: true,
+            ..MouseEvent::new(MouseEventKind::Drag(MouseButton::Left), 0, 0)
+        };
+
+        let normal = vt100::MouseProtocolMode::PressRelease;
+        assert_eq!(
+            encode(press, normal, MouseEncoding::Default).unwrap(),
+            b"\x1b[M\x20\x25\x23"
+        );
+        assert_eq!(
+            encode(release, normal, MouseEncoding::Default).unwrap(),
+            b"\x1b[M\x23\x25\x23"
+        );
+        assert_eq!(
+            encode(release, normal, MouseEncoding::Sgr).unwrap(),
+            b"\x1b[<2;5;3m"
+        );
+        assert_eq!(
+            encode(press, normal, MouseEncoding::Urxvt).unwrap(),
+            b"\x1b[32;5;3M"
+        );
+        assert_eq!(encode(drag, normal, MouseEncoding::Sgr), None);
+        assert_eq!(
+            encode(
+                drag,
+                vt100::MouseProtocolMode::ButtonMotion,
+                MouseEncoding::Sgr
+            )
+            .unwrap(),
+            b"\x1b[<48;1;1M"
+        );
+        assert_eq!(
+            encode(
+                release,
+                vt100::MouseProtocolMode::Press,
+                MouseEncoding::Default
+            ),
+            None
+        );
+
+        let far =
+            MouseEvent::new(MouseEventKind::Press(MouseButton::Left), 0, 300);
+        assert_eq!(encode(far, normal, MouseEncoding::Default), None);
+        assert_eq!(
+            encode(far, normal, MouseEncoding::Utf8).unwrap(),
+            "\x1b[M\u{20}\u{14d}\u{21}".as_bytes()
+        );
+    }
+}
@@ -0,0 +1,131 @@
+// the status bits of the control byte at the start of each packet, from
+// <sys/ioctl.h> (libc doesn't export these)
+const TIOCPKT_DATA: u8 = 0;
+const TIOCPKT_FLUSHREAD: u8 = 1;
+const TIOCPKT_FLUSHWRITE: u8 = 2;
+const TIOCPKT_STOP: u8 = 4;
+const TIOCPKT_START: u8 = 8;
+const TIOCPKT_NOSTOP: u8 = 16;
+const TIOCPKT_DOSTOP: u8 = 32;
+
+/// A flow control change reported by the pty in packet mode, via
+/// `Event::FlowControl`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum FlowControl {
+    /// The pty's input queue was flushed.
+    FlushRead,
+
+    /// The pty's output queue was flushed.
+    FlushWrite,
+
+    /// Output was stopped (for instance by the process receiving `^S`).
+    Stop,
+
+    /// Output was restarted (for instance by the process receiving `^Q`).
+    Start,
+
+    /// Software flow control (`IXON`) was disabled, so `^S` and `^Q` are no
+    /// longer interpreted by the pty. Programs which switch to raw mode
+    /// typically generate this.
+    NoStop,
+
+    /// Software flow control (`IXON`) was enabled, with `^S` and `^Q` as the
+    /// stop and start characters.
+    DoStop,
+}
+
+const FLOW_CONTROL_BITS: &[(u8, FlowControl)] = &[
+    (TIOCPKT_FLUSHREAD, FlowControl::FlushRead),
+    (TIOCPKT_FLUSHWRITE, FlowControl::FlushWrite),
+    (TIOCPKT_STOP, FlowControl::Stop),
+    (TIOCPKT_START, FlowControl::Start),
+    (TIOCPKT_NOSTOP, FlowControl::NoStop),
+    (TIOCPKT_DOSTOP, FlowControl::DoStop),
+];
+
+pub fn enable(fd: std::os::unix::io::RawFd) -> std::io::Result<()> {
+    let on: libc::c_int = 1;
+    // safe because TIOCPKT only reads the int we pass
+    if unsafe { libc::ioctl(fd, libc::TIOCPKT, std::ptr::addr_of!(on)) } != 0
+    {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+// a packet read from the pty in packet mode
+#[derive(Debug, PartialEq, Eq)]
+pub struct Packet<'a> {
+    // the flow control changes reported by the control byte
+    pub changes: Vec<FlowControl>,
+    // the output data which follows the control byte. control packets never
+    // contain any data.
+    pub data: &'a [u8],
+}
+
+pub fn parse(packet: &[u8]) -> Packet<'_> {
+    match packet.split_first() {
+        Some((&TIOCPKT_DATA, data)) => Packet {
+            changes: vec![],
+            data,
+        },
+        Some((&status, _)) => Packet {
+            changes: FLOW_CONTROL_BITS
+                .iter()
+                .filter(|(bit, _)| status & bit != 0)
+                .map(|(_, change)| *change)
+                .collect(),
+            data: &[],
+        },
+        None => Packet {
+            changes: vec![],
+            data: &[],
+        },
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            parse(b"\x00abc"),
+            Packet {
+                changes: vec![],
+                data: b"abc",
+            }
+        );
+        assert_eq!(
+            parse(b""),
+            Packet {
+                changes: vec![],
+                data: b"",
+            }
+        );
+        assert_eq!(
+            parse(b"\x10"),
+            Packet {
+                changes: vec![FlowControl::NoStop],
+                data: b"",
+            }
+        );
+        assert_eq!(
+            parse(b"\x05"),
+            Packet {
+                changes: vec![FlowControl::FlushRead, FlowControl::Stop],
+                data: b"",
+            }
+        );
+        // TIOCPKT_IOCTL on its own carries no flow control changes
+        assert_eq!(
+            parse(b"\x40"),
+            Packet {
+                changes: vec![],
+                data: b"",
+            }
+        );
+    }
+}
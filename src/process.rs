@@ -1,13 +1,15 @@
 use futures::future::Future as _;
 use snafu::ResultExt as _;
+use std::convert::TryFrom as _;
 use std::os::unix::io::AsRawFd as _;
 use tokio::io::{AsyncRead as _, AsyncWrite as _};
-use tokio_pty_process::{CommandExt as _, PtyMaster as _};
+use tokio_pty_process::CommandExt as _;
 
 const READ_BUFFER_SIZE: usize = 4 * 1024;
 
 /// Represents events generated by the process.
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum Event {
     /// Emitted once the command has been successfully spawned.
     CommandStart { cmd: String, args: Vec<String> },
@@ -16,13 +18,113 @@ pub enum Event {
     /// process is running under a pty, both stdout and stderr are attached to
     /// the single pty input - there is no way to differentiate them when
     /// reading from the pty output.
+    ///
+    /// When terminal state tracking is enabled, output which generates other
+    /// events (such as `PromptStart` or `TitleChanged`) is split into
+    /// multiple `Output` events, with each of those events returned directly
+    /// after the `Output` event containing the sequence which generated it.
     Output { data: Vec<u8> },
 
     /// Emitted when the command has exited.
     CommandExit { status: std::process::ExitStatus },
 
-    /// Emitted by a `ResizingProcess` when a resize event happens
-    Resize { size: (u16, u16) },
+    /// Emitted by a `ResizingProcess` when a resize event happens.
+    /// `pixel_size` is the size of the terminal in pixels, as (width,
+    /// height), or (0, 0) if it is unknown.
+    Resize {
+        size: (u16, u16),
+        pixel_size: (u16, u16),
+    },
+
+    /// Emitted by a `ScreenProcess` after a chunk of output has been applied
+    /// to the screen.
+    ScreenUpdated,
+
+    /// Emitted by a `ScreenProcess` with diffs enabled after each
+    /// `ScreenUpdated` event, containing the changes to the screen since the
+    /// previous `ScreenDiff` event.
+    ScreenDiff { diff: crate::diff::ScreenDiff },
+
+    /// Emitted when the process changes the window title (via `OSC 0` or
+    /// `OSC 2`, or by restoring a title with `CSI 23 t`).
+    TitleChanged { title: String },
+
+    /// Emitted when the process changes the icon name (via `OSC 0` or
+    /// `OSC 1`, or by restoring an icon name with `CSI 23 t`).
+    IconNameChanged { icon_name: String },
+
+    /// Emitted when the process reports a new working directory via
+    /// `OSC 7 ; file://<host>/<path>`. Note that `host` may refer to a
+    /// different machine (for instance if the process is running `ssh`).
+    CwdChanged {
+        host: String,
+        cwd: std::path::PathBuf,
+    },
+
+    /// Emitted when a shell with prompt markers enabled starts drawing its
+    /// prompt (`OSC 133 ; A`).
+    PromptStart,
+
+    /// Emitted when a shell with prompt markers enabled has finished
+    /// drawing its prompt, and the user has started typing a command
+    /// (`OSC 133 ; B`).
+    CommandInput,
+
+    /// Emitted when a shell with prompt markers enabled starts running a
+    /// command, so any following output belongs to that command
+    /// (`OSC 133 ; C`).
+    CommandOutputStart,
+
+    /// Emitted when a command run by a shell with prompt markers enabled
+    /// has finished (`OSC 133 ; D`). `exit_code` is `None` if the shell did
+    /// not report one.
+    CommandFinished { exit_code: Option<i32> },
+
+    /// Emitted when the process copies data to the clipboard via `OSC 52`.
+    /// `selection` is the selection which was set (for instance `"c"` for
+    /// the clipboard or `"p"` for the primary selection), and `data` is the
+    /// decoded data. If the process sets several selections at once, this
+    /// is emitted once for each of them. `data` is empty if the process
+    /// cleared the selection (including by sending invalid data).
+    ClipboardSet { selection: String, data: Vec<u8> },
+
+    /// Emitted when the process closes a hyperlink (`OSC 8`), containing
+    /// the link along with the text it covered. `link.offset` counts all of
+    /// the output read since terminal state tracking was enabled. Links
+    /// which are still open when the process exits are not reported.
+    Hyperlink { link: crate::hyperlink::Hyperlink },
+
+    /// Emitted when the process rings the terminal bell.
+    Bell,
+
+    /// Emitted when the process enables or disables one of the terminal
+    /// modes described by `Mode`. The current state of each mode is also
+    /// available via `Process::mode`.
+    ModeChanged {
+        mode: crate::mode::Mode,
+        enabled: bool,
+    },
+
+    /// Emitted in packet mode when the pty reports a flow control change,
+    /// such as output being stopped with `^S`.
+    FlowControl { change: crate::packet::FlowControl },
+
+    /// Emitted in packet mode when the process has changed its terminal
+    /// attributes, for instance by switching to raw mode or disabling echo.
+    /// See `Process::enable_packet_mode` for when changes are reported.
+    TermiosChanged { termios: crate::termios::Termios },
+}
+
+struct Tee {
+    sink: Box<dyn tokio::io::AsyncWrite + Send>,
+    buf: std::collections::VecDeque<u8>,
+    needs_flush: bool,
+}
+
+impl Tee {
+    fn is_pending(&self) -> bool {
+        !self.buf.is_empty() || self.needs_flush
+    }
 }
 
 struct State {
@@ -58,6 +160,9 @@ impl State {
 /// the stream will return an event when the process starts, when it writes
 /// output to the pty, and when it exits. See the `Event` type for more
 /// details.
+#[must_use = "streams do nothing unless polled"]
+// the flags track independent parts of the stream state
+#[allow(clippy::struct_excessive_bools)]
 pub struct Process<R: tokio::io::AsyncRead> {
     state: State,
     input: R,
@@ -67,9 +172,23 @@ pub struct Process<R: tokio::io::AsyncRead> {
     buf: [u8; READ_BUFFER_SIZE],
     started: bool,
     exited: bool,
-    needs_resize: Option<(u16, u16)>,
+    needs_resize: bool,
+    size: Option<(u16, u16)>,
+    pixel_size: (u16, u16),
     stdin_closed: bool,
     stdout_closed: bool,
+    event_time: Option<std::time::Instant>,
+    tees: Vec<Tee>,
+    transcript: Option<crate::transcript::Transcript>,
+    subscribers: Vec<crate::subscribe::Subscriber>,
+    term: Option<crate::term::Terminal>,
+    cwd_pid: Option<u32>,
+    pending_events: std::collections::VecDeque<Event>,
+    shell_integration: bool,
+    integration: Option<crate::shell::ShellIntegration>,
+    termios_config: Option<crate::termios::TermiosConfig>,
+    packet_mode: bool,
+    termios: Option<crate::termios::Termios>,
 }
 
 impl<R: tokio::io::AsyncRead + 'static> Process<R> {
@@ -92,17 +211,68 @@ impl<R: tokio::io::AsyncRead + 'static> Process<R> {
             buf: [0; READ_BUFFER_SIZE],
             started: false,
             exited: false,
-            needs_resize: None,
+            needs_resize: false,
+            size: None,
+            pixel_size: (0, 0),
             stdin_closed: false,
             stdout_closed: false,
+            event_time: None,
+            tees: vec![],
+            transcript: None,
+            subscribers: vec![],
+            term: None,
+            cwd_pid: None,
+            pending_events: std::collections::VecDeque::new(),
+            shell_integration: false,
+            integration: None,
+            termios_config: None,
+            packet_mode: false,
+            termios: None,
         }
     }
 
     /// Requests a change to the pty's terminal size.
     ///
-    /// This will only be applied on the next call to `poll`.
+    /// This will only be applied on the next call to `poll`. The size of
+    /// the pty in pixels will be reset to unknown (zero) - use
+    /// `resize_with_pixels` to set it as well.
     pub fn resize(&mut self, rows: u16, cols: u16) {
-        self.needs_resize = Some((rows, cols));
+        self.resize_with_pixels(rows, cols, 0, 0);
+    }
+
+    /// Requests a change to the pty's terminal size, including its size in
+    /// pixels (`ws_xpixel` and `ws_ypixel`), which programs that display
+    /// images use to determine the size of each cell. A width or height of
+    /// zero means that the value is unknown.
+    ///
+    /// This will only be applied on the next call to `poll`.
+    pub fn resize_with_pixels(
+        &mut self,
+        rows: u16,
+        cols: u16,
+        width: u16,
+        height: u16,
+    ) {
+        self.needs_resize = true;
+        self.size = Some((rows, cols));
+        self.pixel_size = (width, height);
+        if let Some(term) = &mut self.term {
+            term.set_size(rows, cols);
+        }
+    }
+
+    /// Returns the most recently requested terminal size for the pty, if
+    /// `resize` has been called (or if terminal state tracking set the
+    /// default size - see `track_terminal_state`).
+    pub fn size(&self) -> Option<(u16, u16)> {
+        self.size
+    }
+
+    /// Returns the most recently requested size of the pty in pixels, as
+    /// (width, height), if `resize` or `resize_with_pixels` has been
+    /// called. Dimensions which are unknown are zero.
+    pub fn pixel_size(&self) -> Option<(u16, u16)> {
+        self.size.map(|_| self.pixel_size)
     }
 
     /// Returns a mutable reference to the input object provided in the
@@ -113,6 +283,393 @@ impl<R: tokio::io::AsyncRead + 'static> Process<R> {
     pub fn input(&mut self) -> &mut R {
         &mut self.input
     }
+
+    /// Adds a sink which will receive a copy of all output read from the pty.
+    ///
+    /// This can be called multiple times to write the output to multiple
+    /// sinks. Output is written to each sink in the order it was read, and
+    /// no more output will be read from the pty until all sinks have
+    /// accepted (and flushed) the previous output, so a slow sink will apply
+    /// backpressure to the process rather than causing output to be buffered
+    /// indefinitely. `Event::Output` events are still emitted as usual.
+    pub fn tee<W: tokio::io::AsyncWrite + Send + 'static>(
+        &mut self,
+        sink: W,
+    ) {
+        self.tees.push(Tee {
+            sink: Box::new(sink),
+            buf: std::collections::VecDeque::new(),
+            needs_flush: false,
+        });
+    }
+
+    /// Starts retaining the most recent output of the process, up to the
+    /// given limit.
+    ///
+    /// The retained output can be retrieved at any point via `transcript`.
+    /// Calling this again replaces the existing transcript.
+    pub fn keep_transcript(
+        &mut self,
+        limit: crate::transcript::TranscriptLimit,
+    ) {
+        self.transcript = Some(crate::transcript::Transcript::new(limit));
+    }
+
+    /// Returns the transcript of recent output, if `keep_transcript` has
+    /// been called.
+    pub fn transcript(&self) -> Option<&crate::transcript::Transcript> {
+        self.transcript.as_ref()
+    }
+
+    /// Creates a new subscription to the events generated by this process.
+    ///
+    /// Each subscription receives its own copy of every event returned by
+    /// this stream (subject to the filter in `options`), starting with a
+    /// replay of the `CommandStart` event and the contents of the transcript
+    /// (if one is being kept) for processes which have already started.
+    /// Subscriptions only receive events while this stream is being polled,
+    /// so if all consumers use subscriptions, this stream still needs to be
+    /// driven separately (for instance by spawning it onto the runtime with
+    /// `for_each`).
+    ///
+    /// Events which are added by wrappers around this stream (such as
+    /// `Resize` from `ResizingProcess` or `ScreenUpdated` and `ScreenDiff`
+    /// from `ScreenProcess`) are not sent to subscriptions.
+    ///
+    /// If a subscriber which doesn't use `LagPolicy::Block` still has
+    /// undelivered events when this stream ends, its subscription ends with
+    /// a `SubscriberLagged` error.
+    pub fn subscribe(
+        &mut self,
+        options: crate::subscribe::SubscribeOptions,
+    ) -> crate::subscribe::Subscription {
+        let mut replay = vec![];
+        if self.started {
+            replay.push(Event::CommandStart {
+                cmd: self.cmd.clone(),
+                args: self.args.clone(),
+            });
+        }
+        if let Some(transcript) = &self.transcript {
+            replay.extend(transcript.replay());
+        }
+        let (subscriber, subscription) =
+            crate::subscribe::Subscriber::new(options, replay);
+        self.subscribers.push(subscriber);
+        subscription
+    }
+
+    /// Starts answering terminal queries sent by the process.
+    ///
+    /// Since there is no real terminal attached to the pty, programs which
+    /// query the terminal (for the cursor position, device attributes,
+    /// terminal version, etc) will otherwise never receive a response, which
+    /// can cause them to hang or misbehave. When this is enabled, the output
+    /// of the process is watched for these queries, and responses based on
+    /// `profile` (and the current state of the terminal) are written back to
+    /// the pty.
+    pub fn respond_to_queries(
+        &mut self,
+        profile: crate::query::TerminalProfile,
+    ) {
+        self.term_mut().set_profile(Some(profile));
+    }
+
+    /// Starts tracking the state of the terminal that the process is
+    /// drawing to.
+    ///
+    /// This feeds all output from the process through a terminal parser,
+    /// which is required for `screen`, `mode`, `mouse_protocol`,
+    /// `send_mouse`, `title`, and `icon_name`, and for the `Bell`,
+    /// `ModeChanged`, `TitleChanged`, `IconNameChanged`, `CwdChanged`,
+    /// `ClipboardSet`, `Hyperlink`, and shell integration marker events. Since parsing
+    /// all of the output has a cost, it is disabled by default, but it is
+    /// enabled automatically by `respond_to_queries`,
+    /// `install_shell_integration`, and `set_clipboard_read_policy`, and by
+    /// wrapping this process in a `ScreenProcess`.
+    ///
+    /// Only output read after this is called is tracked. If no size has been
+    /// requested via `resize`, the pty is resized to 24x80 (the default size
+    /// of the terminal model) so that the size reported by the pty matches
+    /// the size used to answer queries.
+    pub fn track_terminal_state(&mut self) {
+        self.term_mut();
+    }
+
+    /// Configures the shell to report prompt and command boundaries when it
+    /// is spawned.
+    ///
+    /// Most shells don't emit the `OSC 133` markers which generate the
+    /// `PromptStart`, `CommandInput`, `CommandOutputStart`, and
+    /// `CommandFinished` events by default. When this is enabled and the
+    /// command is `bash` or `zsh`, a small integration script is installed
+    /// into a temporary directory (via `--rcfile` for bash and `ZDOTDIR` for
+    /// zsh) which sources the user's own startup files as usual and then
+    /// adds hooks to emit these markers. For other commands, this has no
+    /// effect.
+    ///
+    /// For bash, the hook is a `DEBUG` trap, which runs before any `DEBUG`
+    /// trap already set by the user's `~/.bashrc`.
+    ///
+    /// Note that bash ignores `--rcfile` for login shells and
+    /// non-interactive shells, so no markers will be reported in those
+    /// cases. This must be called before the stream is first polled.
+    pub fn install_shell_integration(&mut self) {
+        self.shell_integration = true;
+        self.track_terminal_state();
+    }
+
+    /// Sets how requests from the process to read the clipboard are
+    /// answered.
+    ///
+    /// By default, read requests are ignored. See `ClipboardReadPolicy` for
+    /// the other options. Requests to write to the clipboard are always
+    /// reported as `Event::ClipboardSet` events, regardless of this setting.
+    pub fn set_clipboard_read_policy(
+        &mut self,
+        policy: crate::clipboard::ClipboardReadPolicy,
+    ) {
+        self.term_mut().set_clipboard_read_policy(policy);
+    }
+
+    /// Returns the current state of the terminal screen, as determined by
+    /// the output of the process so far, or `None` if terminal state
+    /// tracking is not enabled (see `track_terminal_state`).
+    pub fn screen(&self) -> Option<&vt100::Screen> {
+        self.term.as_ref().map(crate::term::Terminal::screen)
+    }
+
+    /// Returns whether the given terminal mode is currently enabled by the
+    /// process.
+    ///
+    /// This is always false unless terminal state tracking is enabled (see
+    /// `track_terminal_state`).
+    pub fn mode(&self, mode: crate::mode::Mode) -> bool {
+        self.term.as_ref().is_some_and(|term| term.mode(mode))
+    }
+
+    /// Returns the mouse reporting mode and encoding currently requested by
+    /// the process.
+    ///
+    /// Mouse reporting is always disabled unless terminal state tracking is
+    /// enabled (see `track_terminal_state`).
+    pub fn mouse_protocol(
+        &self,
+    ) -> (vt100::MouseProtocolMode, crate::mouse::MouseEncoding) {
+        self.term.as_ref().map_or(
+            (
+                vt100::MouseProtocolMode::None,
+                crate::mouse::MouseEncoding::Default,
+            ),
+            |term| {
+                (term.screen().mouse_protocol_mode(), term.mouse_encoding())
+            },
+        )
+    }
+
+    /// Sends a mouse event to the process.
+    ///
+    /// The event is encoded using the mouse reporting mode and encoding
+    /// which the process has enabled (see `mouse_protocol`). Events which
+    /// aren't reported in the current mode (for instance, mouse movement
+    /// when only button presses were requested) or which can't be
+    /// represented in the current encoding (for instance, clicks past
+    /// column 223 with the default encoding) are ignored, as a real
+    /// terminal would do.
+    ///
+    /// The event will only be written to the pty on the next call to
+    /// `poll`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::MouseReportingDisabled` if the process has not
+    /// enabled mouse reporting.
+    pub fn send_mouse(
+        &mut self,
+        event: crate::mouse::MouseEvent,
+    ) -> Result<(), crate::error::Error> {
+        let (mode, encoding) = self.mouse_protocol();
+        if mode == vt100::MouseProtocolMode::None {
+            return Err(crate::error::Error::MouseReportingDisabled);
+        }
+        if let Some(bytes) = crate::mouse::encode(event, mode, encoding) {
+            self.input_buf.extend(bytes);
+        } else {
+            log::debug!("mouse event not reported: {event:?}");
+        }
+        Ok(())
+    }
+
+    /// Sets terminal attributes to apply to the pty before the command is
+    /// spawned.
+    ///
+    /// Since the attributes are applied before the command starts, they are
+    /// guaranteed to be in effect from the first byte of input or output
+    /// (unlike changing them after the command has started, which races
+    /// with the command's own reads). This must be called before the stream
+    /// is first polled.
+    pub fn configure_termios(
+        &mut self,
+        config: crate::termios::TermiosConfig,
+    ) {
+        self.termios_config = Some(config);
+    }
+
+    /// Enables packet mode (`TIOCPKT`) on the pty.
+    ///
+    /// In packet mode, the pty reports flow control changes in-band with
+    /// the output, and these are emitted as `Event::FlowControl` events
+    /// (the control bytes are removed from the data in `Event::Output`).
+    /// In addition, the terminal attributes are checked each time a packet
+    /// is read from the pty, and `Event::TermiosChanged` is emitted (before
+    /// any output in that packet) whenever they differ from the last ones
+    /// seen. Since Linux only sends a control packet for changes to the
+    /// software flow control settings (unless the process has set
+    /// `EXTPROC`), other changes (such as disabling echo for a password
+    /// prompt) are reported with the next output the process writes. This
+    /// must be called before the stream is first polled.
+    pub fn enable_packet_mode(&mut self) {
+        self.packet_mode = true;
+    }
+
+    /// Returns the current terminal attributes of the pty.
+    ///
+    /// This reflects any changes made by the process (for instance, via
+    /// `stty` or a readline library), so it can be used to tell whether the
+    /// process is currently reading a password (with echo disabled), or is
+    /// in raw mode.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::PtyNotOpen` if the stream has not been polled yet, or
+    /// `Error::GetTermios` if the attributes could not be read.
+    pub fn termios(
+        &self,
+    ) -> Result<crate::termios::Termios, crate::error::Error> {
+        let pty = self
+            .state
+            .pty
+            .as_ref()
+            .ok_or(crate::error::Error::PtyNotOpen)?;
+        crate::termios::Termios::from_fd(pty.as_raw_fd())
+            .context(crate::error::GetTermios)
+    }
+
+    /// Returns the current window title, as set by the process.
+    ///
+    /// This is always empty unless terminal state tracking is enabled (see
+    /// `track_terminal_state`).
+    pub fn title(&self) -> &str {
+        self.term.as_ref().map_or("", crate::term::Terminal::title)
+    }
+
+    /// Returns the current icon name, as set by the process.
+    ///
+    /// This is always empty unless terminal state tracking is enabled (see
+    /// `track_terminal_state`).
+    pub fn icon_name(&self) -> &str {
+        self.term
+            .as_ref()
+            .map_or("", crate::term::Terminal::icon_name)
+    }
+
+    /// Returns the current working directory of the process.
+    ///
+    /// This is normally the working directory of the foreground process
+    /// group of the pty (or the spawned process itself), as found in
+    /// `/proc`. If the process has reported a working directory on a
+    /// different host via `OSC 7` (for instance, a shell running under
+    /// `ssh`), that directory is returned instead, for as long as the
+    /// process group which reported it is still in the foreground. On
+    /// systems which don't provide `/proc/<pid>/cwd`, the most recently
+    /// reported directory is returned. Reports are only seen while terminal
+    /// state tracking is enabled (see `track_terminal_state`).
+    pub fn cwd(&self) -> Option<std::path::PathBuf> {
+        let pid = self.foreground_pid();
+        if let Some(term) = &self.term {
+            if let Some(cwd) = term.cwd() {
+                if !is_local_host(term.cwd_host()) && self.cwd_pid == pid {
+                    return Some(cwd.to_path_buf());
+                }
+            }
+        }
+
+        pid.and_then(|pid| {
+            std::fs::read_link(format!("/proc/{pid}/cwd")).ok()
+        })
+        .or_else(|| {
+            self.term
+                .as_ref()
+                .and_then(crate::term::Terminal::cwd)
+                .map(std::path::Path::to_path_buf)
+        })
+    }
+
+    fn foreground_pid(&self) -> Option<u32> {
+        if let Some(pty) = &self.state.pty {
+            // safe because tcgetpgrp only queries the given fd (which stays
+            // open for as long as self.state.pty does) and reports any error
+            // through its return value
+            let pgrp = unsafe { libc::tcgetpgrp(pty.as_raw_fd()) };
+            if let Ok(pgrp) = u32::try_from(pgrp) {
+                if pgrp > 0 {
+                    return Some(pgrp);
+                }
+            }
+        }
+        self.state
+            .process
+            .as_ref()
+            .map(tokio_pty_process::Child::id)
+    }
+
+    pub(crate) fn screen_mut(&mut self) -> &mut vt100::Screen {
+        self.term_mut().screen_mut()
+    }
+
+    pub(crate) fn set_scrollback(&mut self, scrollback_len: usize) {
+        self.term_mut().set_scrollback(scrollback_len);
+    }
+
+    // whether more pieces of the most recently read output are still
+    // waiting to be returned
+    pub(crate) fn has_pending_output(&self) -> bool {
+        self.pending_events
+            .iter()
+            .any(|event| matches!(event, Event::Output { .. }))
+    }
+
+    // creates the terminal model the first time a feature which needs it is
+    // enabled. the model has to start out with some size, so if none has
+    // been requested yet, the pty is given the same size, or else queries
+    // like CPR could be answered using a different size from what
+    // TIOCGWINSZ reports.
+    fn term_mut(&mut self) -> &mut crate::term::Terminal {
+        if self.term.is_none() {
+            let mut term = crate::term::Terminal::new();
+            if let Some((rows, cols)) = self.size {
+                term.set_size(rows, cols);
+            } else {
+                let (rows, cols) = term.screen().size();
+                self.needs_resize = true;
+                self.size = Some((rows, cols));
+                self.pixel_size = (0, 0);
+            }
+            self.term = Some(term);
+        }
+        self.term.as_mut().unwrap()
+    }
+
+    /// Returns the time at which the most recently returned event was
+    /// observed.
+    ///
+    /// For `Output` events this is the time at which the data was read from
+    /// the pty, and for `CommandExit` events this is the time at which the
+    /// process was reaped, so it does not include any delay between the
+    /// event being generated and the stream being polled by the consumer.
+    pub fn last_event_time(&self) -> Option<std::time::Instant> {
+        self.event_time
+    }
 }
 
 impl<R: tokio::io::AsyncRead + 'static> Process<R> {
@@ -129,6 +686,9 @@ impl<R: tokio::io::AsyncRead + 'static> Process<R> {
         // causes an error. also, poll_resize needs to happen after
         // poll_command_start, or else the pty might not be initialized.
         &Self::poll_command_start,
+        &Self::poll_pending_events,
+        &Self::poll_write_tee,
+        &Self::poll_write_subscribers,
         &Self::poll_command_exit,
         &Self::poll_resize,
         &Self::poll_read_stdin,
@@ -136,23 +696,51 @@ impl<R: tokio::io::AsyncRead + 'static> Process<R> {
         &Self::poll_read_stdout,
     ];
 
+    fn is_backlogged(&self) -> bool {
+        self.tees.iter().any(Tee::is_pending)
+            || self
+                .subscribers
+                .iter()
+                .any(crate::subscribe::Subscriber::blocks)
+    }
+
     fn poll_resize(
         &mut self,
     ) -> component_future::Poll<Option<Event>, crate::error::Error> {
-        if let Some((rows, cols)) = &self.needs_resize {
-            component_future::try_ready!(self
-                .state
-                .pty()
-                .resize(*rows, *cols)
-                .context(crate::error::ResizePty));
-            log::debug!("resize({}x{})", cols, rows);
-            self.needs_resize = None;
+        if let (true, Some(size)) = (self.needs_resize, self.size) {
+            crate::resize::set_window_size(
+                self.state.pty().as_raw_fd(),
+                size,
+                self.pixel_size,
+            )
+            .context(crate::error::ResizePty)?;
+            log::debug!(
+                "resize({}x{}, {}x{}px)",
+                size.1,
+                size.0,
+                self.pixel_size.0,
+                self.pixel_size.1
+            );
+            self.needs_resize = false;
             Ok(component_future::Async::DidWork)
         } else {
             Ok(component_future::Async::NothingToDo)
         }
     }
 
+    // the signature is required to match the rest of POLL_FNS
+    #[allow(clippy::unnecessary_wraps)]
+    fn poll_pending_events(
+        &mut self,
+    ) -> component_future::Poll<Option<Event>, crate::error::Error> {
+        Ok(self
+            .pending_events
+            .pop_front()
+            .map_or(component_future::Async::NothingToDo, |event| {
+                component_future::Async::Ready(Some(event))
+            }))
+    }
+
     fn poll_command_start(
         &mut self,
     ) -> component_future::Poll<Option<Event>, crate::error::Error> {
@@ -171,9 +759,44 @@ impl<R: tokio::io::AsyncRead + 'static> Process<R> {
             );
         }
 
+        if self.shell_integration && self.integration.is_none() {
+            self.integration = crate::shell::ShellIntegration::new(&self.cmd)
+                .context(crate::error::ShellIntegration)?;
+            if self.integration.is_none() {
+                log::warn!("no shell integration available for {}", self.cmd);
+            }
+        }
+
         if self.state.process.is_none() {
+            // apply any requested size up front, so that the process never
+            // sees the initial size of 0x0
+            self.poll_resize()?;
+
+            if let Some(config) = &self.termios_config {
+                config
+                    .apply_to_fd(self.state.pty().as_raw_fd())
+                    .context(crate::error::SetTermios)?;
+                log::debug!("configure_termios({config:?})");
+            }
+
+            if self.packet_mode {
+                let fd = self.state.pty().as_raw_fd();
+                crate::packet::enable(fd)
+                    .context(crate::error::EnablePacketMode)?;
+                self.termios = Some(
+                    crate::termios::Termios::from_fd(fd)
+                        .context(crate::error::GetTermios)?,
+                );
+                log::debug!("enable_packet_mode");
+            }
+
+            let mut command = std::process::Command::new(&self.cmd);
+            if let Some(integration) = &self.integration {
+                command.args(integration.args());
+                integration.apply_env(&mut command);
+            }
             self.state.process = Some(
-                std::process::Command::new(&self.cmd)
+                command
                     .args(&self.args)
                     .spawn_pty_async(self.state.pty())
                     .context(crate::error::SpawnProcess {
@@ -187,6 +810,7 @@ impl<R: tokio::io::AsyncRead + 'static> Process<R> {
         }
 
         self.started = true;
+        self.event_time = Some(std::time::Instant::now());
         Ok(component_future::Async::Ready(Some(Event::CommandStart {
             cmd: self.cmd.clone(),
             args: self.args.clone(),
@@ -204,7 +828,7 @@ impl<R: tokio::io::AsyncRead + 'static> Process<R> {
             .input
             .poll_read(&mut self.buf)
             .context(crate::error::ReadTerminal));
-        log::debug!("read_stdin({})", n);
+        log::debug!("read_stdin({n})");
         if n > 0 {
             self.input_buf.extend(self.buf[..n].iter());
         } else {
@@ -228,16 +852,109 @@ impl<R: tokio::io::AsyncRead + 'static> Process<R> {
             .pty_mut()
             .poll_write(buf)
             .context(crate::error::WritePty));
-        log::debug!("write_stdin({})", n);
+        log::debug!("write_stdin({n})");
         for _ in 0..n {
             self.input_buf.pop_front();
         }
         Ok(component_future::Async::DidWork)
     }
 
+    fn poll_write_tee(
+        &mut self,
+    ) -> component_future::Poll<Option<Event>, crate::error::Error> {
+        let mut not_ready = false;
+        let mut did_work = false;
+
+        for tee in &mut self.tees {
+            if !tee.buf.is_empty() {
+                let (a, b) = tee.buf.as_slices();
+                let buf = if a.is_empty() { b } else { a };
+                match tee
+                    .sink
+                    .poll_write(buf)
+                    .context(crate::error::WriteTee)?
+                {
+                    futures::Async::Ready(n) => {
+                        log::debug!("write_tee({n})");
+                        for _ in 0..n {
+                            tee.buf.pop_front();
+                        }
+                        tee.needs_flush = true;
+                        did_work = true;
+                    }
+                    futures::Async::NotReady => {
+                        not_ready = true;
+                    }
+                }
+            } else if tee.needs_flush {
+                match tee.sink.poll_flush().context(crate::error::WriteTee)? {
+                    futures::Async::Ready(()) => {
+                        log::debug!("flush_tee");
+                        tee.needs_flush = false;
+                        did_work = true;
+                    }
+                    futures::Async::NotReady => {
+                        not_ready = true;
+                    }
+                }
+            }
+        }
+
+        if did_work {
+            Ok(component_future::Async::DidWork)
+        } else if not_ready {
+            Ok(component_future::Async::NotReady)
+        } else {
+            Ok(component_future::Async::NothingToDo)
+        }
+    }
+
+    // the signature is required to match the rest of POLL_FNS
+    #[allow(clippy::unnecessary_wraps)]
+    fn poll_write_subscribers(
+        &mut self,
+    ) -> component_future::Poll<Option<Event>, crate::error::Error> {
+        let mut not_ready = false;
+        let mut did_work = false;
+
+        self.subscribers.retain_mut(|subscriber| {
+            match subscriber.poll_flush() {
+                Ok(component_future::Async::DidWork) => {
+                    did_work = true;
+                    true
+                }
+                Ok(component_future::Async::NotReady) => {
+                    not_ready = true;
+                    true
+                }
+                Ok(_) => true,
+                Err(()) => {
+                    log::debug!("subscriber disconnected");
+                    did_work = true;
+                    false
+                }
+            }
+        });
+
+        if did_work {
+            Ok(component_future::Async::DidWork)
+        } else if not_ready {
+            Ok(component_future::Async::NotReady)
+        } else {
+            Ok(component_future::Async::NothingToDo)
+        }
+    }
+
     fn poll_read_stdout(
         &mut self,
     ) -> component_future::Poll<Option<Event>, crate::error::Error> {
+        // don't read any more output until all of the tee sinks and blocking
+        // subscribers have caught up, so that they apply backpressure to the
+        // process
+        if self.stdout_closed || self.is_backlogged() {
+            return Ok(component_future::Async::NothingToDo);
+        }
+
         match self
             .state
             .pty_mut()
@@ -245,11 +962,56 @@ impl<R: tokio::io::AsyncRead + 'static> Process<R> {
             .context(crate::error::ReadPty)
         {
             Ok(futures::Async::Ready(n)) => {
-                log::debug!("read_stdout({})", n);
-                let bytes = self.buf[..n].to_vec();
-                Ok(component_future::Async::Ready(Some(Event::Output {
-                    data: bytes,
-                })))
+                self.event_time = Some(std::time::Instant::now());
+                log::debug!("read_stdout({n})");
+                let bytes = if self.packet_mode {
+                    self.read_packet(n)?
+                } else {
+                    self.buf[..n].to_vec()
+                };
+                if self.packet_mode && bytes.is_empty() {
+                    return Ok(component_future::Async::DidWork);
+                }
+                for tee in &mut self.tees {
+                    tee.buf.extend(bytes.iter());
+                }
+                if let Some(transcript) = &mut self.transcript {
+                    transcript.push(&bytes);
+                }
+                let mut cwd_reported = false;
+                let events = if let Some(term) = &mut self.term {
+                    let events = term.process(&bytes);
+                    self.input_buf.extend(term.take_replies());
+                    cwd_reported = term.take_cwd_reported();
+                    events
+                } else {
+                    vec![]
+                };
+                // remember which process reported the working directory, so
+                // that we can tell when the report is no longer relevant
+                if cwd_reported {
+                    self.cwd_pid = self.foreground_pid();
+                }
+
+                // split the output around the events it generated, so that
+                // the events are returned at the point in the output where
+                // they occurred
+                let mut start = 0;
+                for (offset, event) in events {
+                    if offset > start {
+                        self.pending_events.push_back(Event::Output {
+                            data: bytes[start..offset].to_vec(),
+                        });
+                        start = offset;
+                    }
+                    self.pending_events.push_back(event);
+                }
+                if start < bytes.len() {
+                    self.pending_events.push_back(Event::Output {
+                        data: bytes[start..].to_vec(),
+                    });
+                }
+                Ok(component_future::Async::DidWork)
             }
             Ok(futures::Async::NotReady) => {
                 Ok(component_future::Async::NotReady)
@@ -258,7 +1020,9 @@ impl<R: tokio::io::AsyncRead + 'static> Process<R> {
                 // XXX this seems to be how eof is returned, but this seems...
                 // wrong? i feel like there has to be a better way to do this
                 if let crate::error::Error::ReadPty { source } = &e {
-                    if source.kind() == std::io::ErrorKind::Other {
+                    if source.kind() == std::io::ErrorKind::Other
+                        || source.raw_os_error() == Some(libc::EIO)
+                    {
                         log::debug!("read_stdout(eof)");
                         self.stdout_closed = true;
                         return Ok(component_future::Async::DidWork);
@@ -269,10 +1033,44 @@ impl<R: tokio::io::AsyncRead + 'static> Process<R> {
         }
     }
 
+    // reports the flow control changes and terminal attribute changes for
+    // the packet in the read buffer, and returns its output data
+    fn read_packet(
+        &mut self,
+        n: usize,
+    ) -> Result<Vec<u8>, crate::error::Error> {
+        let packet = crate::packet::parse(&self.buf[..n]);
+        let data = packet.data.to_vec();
+        self.pending_events.extend(
+            packet
+                .changes
+                .into_iter()
+                .map(|change| Event::FlowControl { change }),
+        );
+
+        // the pty only sets TIOCPKT_IOCTL for most changes when EXTPROC is
+        // set, so check the terminal attributes on every packet instead
+        let termios =
+            crate::termios::Termios::from_fd(self.state.pty().as_raw_fd())
+                .context(crate::error::GetTermios)?;
+        if self.termios != Some(termios) {
+            self.termios = Some(termios);
+            self.pending_events
+                .push_back(Event::TermiosChanged { termios });
+        }
+
+        Ok(data)
+    }
+
     fn poll_command_exit(
         &mut self,
     ) -> component_future::Poll<Option<Event>, crate::error::Error> {
         if self.exited {
+            // make sure all output has been written to the tee sinks and
+            // blocking subscribers before ending the stream
+            if self.is_backlogged() {
+                return Ok(component_future::Async::NothingToDo);
+            }
             return Ok(component_future::Async::Ready(None));
         }
         if !self.stdout_closed {
@@ -284,7 +1082,8 @@ impl<R: tokio::io::AsyncRead + 'static> Process<R> {
             .process()
             .poll()
             .context(crate::error::ProcessExitPoll));
-        log::debug!("exit({})", status);
+        self.event_time = Some(std::time::Instant::now());
+        log::debug!("exit({status})");
         self.exited = true;
         Ok(component_future::Async::Ready(Some(Event::CommandExit {
             status,
@@ -292,7 +1091,6 @@ impl<R: tokio::io::AsyncRead + 'static> Process<R> {
     }
 }
 
-#[must_use = "streams do nothing unless polled"]
 impl<R: tokio::io::AsyncRead + 'static> futures::stream::Stream
     for Process<R>
 {
@@ -300,16 +1098,111 @@ impl<R: tokio::io::AsyncRead + 'static> futures::stream::Stream
     type Error = crate::error::Error;
 
     fn poll(&mut self) -> futures::Poll<Option<Self::Item>, Self::Error> {
-        component_future::poll_stream(self, Self::POLL_FNS)
+        let event = futures::try_ready!(component_future::poll_stream(
+            self,
+            Self::POLL_FNS
+        ));
+        if let Some(event) = &event {
+            self.subscribers
+                .retain_mut(|subscriber| subscriber.send(event));
+        } else {
+            for subscriber in &mut self.subscribers {
+                subscriber.finish();
+            }
+            self.subscribers.clear();
+        }
+        Ok(futures::Async::Ready(event))
+    }
+}
+
+// OSC 7 allows the host to be empty, and some shells send localhost
+fn is_local_host(host: &str) -> bool {
+    if host.is_empty() || host == "localhost" {
+        return true;
+    }
+    let mut buf = [0_u8; 256];
+    // safe because gethostname writes at most buf.len() bytes into buf
+    let res =
+        unsafe { libc::gethostname(buf.as_mut_ptr().cast(), buf.len()) };
+    if res != 0 {
+        return false;
     }
+    let len = buf.iter().position(|&c| c == 0).unwrap_or(buf.len());
+    host.as_bytes() == &buf[..len]
 }
 
 #[cfg(test)]
-mod test {
+pub mod test {
     use super::*;
     use futures::sink::Sink as _;
     use futures::stream::Stream as _;
 
+    // runs a shell script with no input
+    pub fn sh(script: &str) -> Process<std::io::Cursor<&'static [u8]>> {
+        Process::new(
+            "sh",
+            &["-c".to_string(), script.to_string()],
+            std::io::Cursor::new(&b""[..]),
+        )
+    }
+
+    // runs a stream to completion, returning all of its events
+    pub fn run<S>(stream: S) -> Vec<Event>
+    where
+        S: futures::stream::Stream<Item = Event, Error = crate::error::Error>
+            + Send
+            + 'static,
+    {
+        tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(stream.collect())
+            .unwrap()
+    }
+
+    // polls a stream until the given marker appears in its output, and
+    // returns the stream so that it can be inspected at that point
+    pub fn wait_for_output<S>(
+        runtime: &mut tokio::runtime::Runtime,
+        stream: S,
+        marker: &[u8],
+    ) -> S
+    where
+        S: futures::stream::Stream<Item = Event, Error = crate::error::Error>
+            + Send
+            + 'static,
+    {
+        let mut stream = stream;
+        let mut output = vec![];
+        loop {
+            let (event, rest) = runtime
+                .block_on(stream.into_future())
+                .map_err(|(e, _)| e)
+                .unwrap();
+            stream = rest;
+            match event {
+                Some(Event::Output { data }) => {
+                    output.extend(data);
+                    if output.windows(marker.len()).any(|w| w == marker) {
+                        return stream;
+                    }
+                }
+                Some(_) => {}
+                None => panic!("unexpected end of stream"),
+            }
+        }
+    }
+
+    // the data from all of the output events
+    pub fn output(events: &[Event]) -> Vec<u8> {
+        let mut output = vec![];
+        for event in events {
+            if let Event::Output { data } = event {
+                output.extend(data.iter());
+            }
+        }
+        output
+    }
+
     #[test]
     fn test_simple() {
         let (wres, rres) = tokio::sync::mpsc::channel(100);
@@ -357,10 +1250,395 @@ mod test {
                     assert!(status.success());
                     exited = true;
                 }
-                Event::Resize { .. } => {}
+                Event::Resize { .. }
+                | Event::ScreenUpdated
+                | Event::ScreenDiff { .. }
+                | Event::TitleChanged { .. }
+                | Event::IconNameChanged { .. }
+                | Event::CwdChanged { .. }
+                | Event::PromptStart
+                | Event::CommandInput
+                | Event::CommandOutputStart
+                | Event::CommandFinished { .. }
+                | Event::ClipboardSet { .. }
+                | Event::Hyperlink { .. }
+                | Event::Bell
+                | Event::ModeChanged { .. }
+                | Event::FlowControl { .. }
+                | Event::TermiosChanged { .. } => {}
             }
         }
         assert!(exited);
         assert_eq!(output, b"hello world\r\nhello world\r\n");
     }
+
+    #[derive(Clone, Default)]
+    struct SharedBuf(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl std::io::Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl tokio::io::AsyncWrite for SharedBuf {
+        fn shutdown(&mut self) -> futures::Poll<(), std::io::Error> {
+            Ok(futures::Async::Ready(()))
+        }
+    }
+
+    #[test]
+    fn test_tee() {
+        let tee1 = SharedBuf::default();
+        let tee2 = SharedBuf::default();
+        let buf = std::io::Cursor::new(b"hello world\n");
+        let mut process = Process::new("cat", &[], buf);
+        process.tee(tee1.clone());
+        process.tee(tee2.clone());
+        let output = output(&run(process));
+        assert_eq!(output, b"hello world\r\nhello world\r\n");
+        assert_eq!(*tee1.0.lock().unwrap(), output);
+        assert_eq!(*tee2.0.lock().unwrap(), output);
+    }
+
+    #[test]
+    fn test_subscribe() {
+        let buf = std::io::Cursor::new(b"hello world\n");
+        let mut process = Process::new("cat", &[], buf);
+        let all = process.subscribe(crate::SubscribeOptions::default());
+        let output = process.subscribe(crate::SubscribeOptions {
+            filter: Some(Box::new(|e| matches!(e, Event::Output { .. }))),
+            ..crate::SubscribeOptions::default()
+        });
+
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        let events = runtime.block_on(process.collect()).unwrap();
+        let all = runtime.block_on(all.collect()).unwrap();
+        let output = runtime.block_on(output.collect()).unwrap();
+
+        assert_eq!(all, events);
+        assert_eq!(
+            output,
+            events
+                .into_iter()
+                .filter(|e| matches!(e, Event::Output { .. }))
+                .collect::<Vec<_>>()
+        );
+        assert!(!output.is_empty());
+    }
+
+    #[test]
+    fn test_cwd() {
+        let process = sh("cd /tmp && printf x && sleep 1");
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        let process = wait_for_output(&mut runtime, process, b"x");
+        assert_eq!(process.cwd(), Some(std::path::PathBuf::from("/tmp")));
+        runtime.block_on(process.collect()).unwrap();
+
+        // a local report which has gone stale
+        let mut process =
+            sh(r"printf '\033]7;file:///nonexistent\007'; cd /tmp; \
+              printf done; sleep 1");
+        process.track_terminal_state();
+        let process = wait_for_output(&mut runtime, process, b"done");
+        assert_eq!(process.cwd(), Some(std::path::PathBuf::from("/tmp")));
+        runtime.block_on(process.collect()).unwrap();
+
+        // a report from a remote host
+        let mut process =
+            sh(r"printf '\033]7;file://remote.invalid/srv\007x'; sleep 1");
+        process.track_terminal_state();
+        let process = wait_for_output(&mut runtime, process, b"x");
+        assert_eq!(process.cwd(), Some(std::path::PathBuf::from("/srv")));
+        runtime.block_on(process.collect()).unwrap();
+    }
+
+    #[test]
+    fn test_termios() {
+        // the script waits for the test to create (and then remove) this
+        // file, so that each check happens at a known point in the script
+        let flag = std::env::temp_dir().join(format!(
+            "{}-test-termios-{}",
+            env!("CARGO_PKG_NAME"),
+            std::process::id()
+        ));
+        let process = sh(&format!(
+            "printf x; until [ -e '{0}' ]; do sleep 0.01; done; \
+             stty -echo; printf y; while [ -e '{0}' ]; do sleep 0.01; done",
+            flag.display()
+        ));
+        assert!(process.termios().is_err());
+
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        let process = wait_for_output(&mut runtime, process, b"x");
+        let termios = process.termios().unwrap();
+        assert!(termios.echo);
+        assert!(termios.canonical);
+        assert!(!termios.is_raw());
+        assert_eq!(termios.intr, Some(b'\x03'));
+        assert_eq!(termios.eof, Some(b'\x04'));
+
+        std::fs::write(&flag, b"").unwrap();
+        let process = wait_for_output(&mut runtime, process, b"y");
+        let termios = process.termios().unwrap();
+        assert!(!termios.echo);
+        assert!(termios.canonical);
+
+        std::fs::remove_file(&flag).unwrap();
+        runtime.block_on(process.collect()).unwrap();
+    }
+
+    #[test]
+    fn test_resize_with_pixels() {
+        let mut process = sh("printf x");
+        assert_eq!(process.pixel_size(), None);
+        process.resize_with_pixels(24, 80, 640, 384);
+        assert_eq!(process.size(), Some((24, 80)));
+        assert_eq!(process.pixel_size(), Some((640, 384)));
+
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        let mut process = wait_for_output(&mut runtime, process, b"x");
+        assert_eq!(
+            crate::resize::window_size(process.state.pty().as_raw_fd())
+                .unwrap(),
+            ((24, 80), (640, 384))
+        );
+
+        process.resize(30, 100);
+        assert_eq!(process.pixel_size(), Some((0, 0)));
+    }
+
+    #[test]
+    fn test_track_terminal_state() {
+        let mut process = sh("stty size");
+        assert!(process.screen().is_none());
+        assert_eq!(process.size(), None);
+        process.track_terminal_state();
+        assert_eq!(process.size(), Some((24, 80)));
+
+        assert_eq!(output(&run(process)), b"24 80\r\n");
+    }
+
+    #[test]
+    fn test_packet_mode() {
+        let mut process = sh(
+            "printf a; stty -echo; printf b; stty -icanon -ixon; printf c",
+        );
+        process.enable_packet_mode();
+        let events = run(process);
+
+        let mut output: Vec<u8> = vec![];
+        let mut changes = vec![];
+        // the output seen before each terminal attribute change
+        let mut termios = vec![];
+        for event in events {
+            match event {
+                Event::Output { data } => output.extend(data.iter()),
+                Event::FlowControl { change } => changes.push(change),
+                Event::TermiosChanged { termios: t } => {
+                    termios.push((output.clone(), t));
+                }
+                _ => {}
+            }
+        }
+        assert_eq!(output, b"abc");
+        assert_eq!(changes, vec![crate::FlowControl::NoStop]);
+
+        // changes are reported before any output which was written after
+        // them, even when the pty doesn't send a control packet for them
+        let (before, _) = termios.iter().find(|(_, t)| !t.echo).unwrap();
+        assert!(!before.contains(&b'b'));
+        let (before, _) = termios.iter().find(|(_, t)| !t.canonical).unwrap();
+        assert!(!before.contains(&b'c'));
+    }
+
+    #[test]
+    fn test_configure_termios() {
+        let buf = std::io::Cursor::new(b"hello world\n");
+        let mut process = Process::new("cat", &[], buf);
+        process.configure_termios(crate::TermiosConfig {
+            echo: Some(false),
+            output_processing: Some(false),
+            ..crate::TermiosConfig::default()
+        });
+        assert_eq!(output(&run(process)), b"hello world\n");
+    }
+
+    #[test]
+    fn test_event_order() {
+        let mut process = Process::new(
+            "printf",
+            &[r"\033]133;C\007out\033]133;D;0\007\033]133;A\007$ "
+                .to_string()],
+            std::io::Cursor::new(b""),
+        );
+        process.track_terminal_state();
+        let events = run(process);
+
+        // the output may be read in more than one chunk, so combine
+        // adjacent output events
+        let mut merged: Vec<Event> = vec![];
+        for event in events {
+            match (merged.last_mut(), event) {
+                (
+                    Some(Event::Output { data: prev }),
+                    Event::Output { data },
+                ) => prev.extend(data),
+                (
+                    _,
+                    Event::CommandStart { .. } | Event::CommandExit { .. },
+                ) => {}
+                (_, event) => merged.push(event),
+            }
+        }
+        assert_eq!(
+            merged,
+            vec![
+                Event::Output {
+                    data: b"\x1b]133;C\x07".to_vec()
+                },
+                Event::CommandOutputStart,
+                Event::Output {
+                    data: b"out\x1b]133;D;0\x07".to_vec()
+                },
+                Event::CommandFinished { exit_code: Some(0) },
+                Event::Output {
+                    data: b"\x1b]133;A\x07".to_vec()
+                },
+                Event::PromptStart,
+                Event::Output {
+                    data: b"$ ".to_vec()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_shell_integration() {
+        use std::os::unix::fs::PermissionsExt as _;
+
+        // removes the test directory even if an assertion fails
+        struct TestDir(std::path::PathBuf);
+
+        impl Drop for TestDir {
+            fn drop(&mut self) {
+                let _ = std::fs::remove_dir_all(&self.0);
+            }
+        }
+
+        // run bash with a HOME of our own, so that the user's ~/.bashrc
+        // can't affect the result. the wrapper has to be called bash for
+        // the integration to be installed.
+        let dir = TestDir(std::env::temp_dir().join(format!(
+            "{}-test-shell-integration-{}",
+            env!("CARGO_PKG_NAME"),
+            std::process::id()
+        )));
+        let home = dir.0.join("home");
+        std::fs::create_dir_all(&home).unwrap();
+        std::fs::write(
+            home.join(".bashrc"),
+            "PS1='$ '\n\
+             trap '__debug_trap=$BASH_COMMAND' DEBUG\n\
+             __pc() { __ran=1; }\n\
+             __pc2() { :; }\n\
+             PROMPT_COMMAND=(__pc __pc2)\n",
+        )
+        .unwrap();
+        let bash = dir.0.join("bash");
+        std::fs::write(
+            &bash,
+            format!(
+                "#!/bin/sh\nHOME='{}' exec bash \"$@\"\n",
+                home.display()
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(
+            &bash,
+            std::fs::Permissions::from_mode(0o755),
+        )
+        .unwrap();
+
+        let mut process = Process::new(
+            bash.to_str().unwrap(),
+            &[],
+            std::io::Cursor::new(
+                b"true\nfalse\necho \"trap=$__debug_trap pc=$__ran\"\nexit\n",
+            ),
+        );
+        process.install_shell_integration();
+        let events = run(process);
+        drop(dir);
+
+        // an existing DEBUG trap and PROMPT_COMMAND still run
+        let output = output(&events);
+        assert!(output.windows(10).any(|w| w == b"trap=echo "));
+        assert!(output.windows(4).any(|w| w == b"pc=1"));
+
+        // the first command can't start before its prompt has been printed,
+        // even if PROMPT_COMMAND runs other commands after ours
+        let first_command = events
+            .iter()
+            .position(|e| *e == Event::CommandOutputStart)
+            .unwrap();
+        assert!(self::output(&events[..first_command])
+            .windows(2)
+            .any(|w| w == b"$ "));
+
+        let marks: Vec<_> = events
+            .into_iter()
+            .filter(|e| {
+                matches!(
+                    e,
+                    Event::PromptStart
+                        | Event::CommandOutputStart
+                        | Event::CommandFinished { .. }
+                )
+            })
+            .collect();
+        assert_eq!(
+            marks,
+            vec![
+                Event::PromptStart,
+                Event::CommandOutputStart,
+                Event::CommandFinished { exit_code: Some(0) },
+                Event::PromptStart,
+                Event::CommandOutputStart,
+                Event::CommandFinished { exit_code: Some(1) },
+                Event::PromptStart,
+                Event::CommandOutputStart,
+                Event::CommandFinished { exit_code: Some(0) },
+                Event::PromptStart,
+                Event::CommandOutputStart,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_respond_to_queries() {
+        let script = r#"
+            stty -icanon -echo
+            printf 'abc\033[6n'
+            IFS= read -r -d R cpr
+            printf '\033[c'
+            IFS= read -r -d c da
+            printf '\ncpr=%s da=%s\n' "${cpr##*[}" "${da##*[}"
+        "#;
+        let mut process = Process::new(
+            "bash",
+            &["-c".to_string(), script.to_string()],
+            std::io::Cursor::new(b""),
+        );
+        process.respond_to_queries(crate::TerminalProfile::default());
+        let events = run(process);
+
+        let output = output(&events);
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("cpr=1;4 da=?62;22"), "{:?}", output);
+    }
 }
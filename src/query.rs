@@ -0,0 +1,262 @@
+/// Describes the terminal that a `Process` pretends to be when answering
+/// queries from the running program.
+///
+/// Programs like readline, fzf, and neovim send queries such as cursor
+/// position reports or device attribute requests to the terminal, and may
+/// block or misbehave if nothing answers. Since a `Process` has no real
+/// terminal attached, `Process::respond_to_queries` can be used to answer
+/// these queries automatically with the values configured here.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TerminalProfile {
+    /// The parameters returned in response to a primary device attributes
+    /// query (`CSI c`). The response will be `CSI ? <value> c`.
+    pub primary_device_attributes: String,
+
+    /// The parameters returned in response to a secondary device attributes
+    /// query (`CSI > c`). The response will be `CSI > <value> c`.
+    pub secondary_device_attributes: String,
+
+    /// The name and version returned in response to an `XTVERSION` query
+    /// (`CSI > q`). The response will be `DCS > | <value> ST`.
+    pub name_and_version: String,
+
+    /// The default foreground color, as (red, green, blue), returned in
+    /// response to `OSC 10 ; ?`.
+    pub foreground: (u8, u8, u8),
+
+    /// The default background color, as (red, green, blue), returned in
+    /// response to `OSC 11 ; ?`.
+    pub background: (u8, u8, u8),
+
+    /// The cursor color, as (red, green, blue), returned in response to
+    /// `OSC 12 ; ?`.
+    pub cursor: (u8, u8, u8),
+
+    /// The indexed color palette, as (red, green, blue), returned in
+    /// response to `OSC 4 ; <index> ; ?`. Queries for indexes past the end of
+    /// this list are not answered.
+    pub palette: Vec<(u8, u8, u8)>,
+}
+
+impl Default for TerminalProfile {
+    fn default() -> Self {
+        Self {
+            // vt220 with ansi color
+            primary_device_attributes: "62;22".to_string(),
+            // vt220, firmware version 10
+            secondary_device_attributes: "1;10;0".to_string(),
+            name_and_version: concat!(
+                env!("CARGO_PKG_NAME"),
+                "(",
+                env!("CARGO_PKG_VERSION"),
+                ")"
+            )
+            .to_string(),
+            // light gray on black
+            foreground: XTERM_COLORS[7],
+            background: XTERM_COLORS[0],
+            cursor: XTERM_COLORS[7],
+            palette: default_palette(),
+        }
+    }
+}
+
+const XTERM_COLORS: [(u8, u8, u8); 16] = [
+    (0x00, 0x00, 0x00),
+    (0xcd, 0x00, 0x00),
+    (0x00, 0xcd, 0x00),
+    (0xcd, 0xcd, 0x00),
+    (0x00, 0x00, 0xee),
+    (0xcd, 0x00, 0xcd),
+    (0x00, 0xcd, 0xcd),
+    (0xe5, 0xe5, 0xe5),
+    (0x7f, 0x7f, 0x7f),
+    (0xff, 0x00, 0x00),
+    (0x00, 0xff, 0x00),
+    (0xff, 0xff, 0x00),
+    (0x5c, 0x5c, 0xff),
+    (0xff, 0x00, 0xff),
+    (0x00, 0xff, 0xff),
+    (0xff, 0xff, 0xff),
+];
+
+const CUBE_LEVELS: [u8; 6] = [0x00, 0x5f, 0x87, 0xaf, 0xd7, 0xff];
+
+/// Returns the default 256 color palette used by xterm.
+fn default_palette() -> Vec<(u8, u8, u8)> {
+    let mut palette = XTERM_COLORS.to_vec();
+    for r in &CUBE_LEVELS {
+        for g in &CUBE_LEVELS {
+            for b in &CUBE_LEVELS {
+                palette.push((*r, *g, *b));
+            }
+        }
+    }
+    for i in 0..24 {
+        let level = 8 + i * 10;
+        palette.push((level, level, level));
+    }
+    palette
+}
+
+fn format_color((r, g, b): (u8, u8, u8)) -> String {
+    format!("rgb:{r:02x}{r:02x}/{g:02x}{g:02x}/{b:02x}{b:02x}")
+}
+
+impl TerminalProfile {
+    pub(crate) fn reply_csi(
+        &self,
+        screen: &vt100::Screen,
+        i1: Option<u8>,
+        params: &[&[u16]],
+        c: char,
+    ) -> Option<Vec<u8>> {
+        let param = params
+            .first()
+            .and_then(|param| param.first())
+            .copied()
+            .unwrap_or(0);
+        match (i1, param, c) {
+            // DSR - device status report
+            (None, 5, 'n') => Some(b"\x1b[0n".to_vec()),
+            // CPR - cursor position report
+            (None, 6, 'n') => {
+                let (row, col) = screen.cursor_position();
+                Some(format!("\x1b[{};{}R", row + 1, col + 1).into_bytes())
+            }
+            // DECXCPR - extended cursor position report
+            (Some(b'?'), 6, 'n') => {
+                let (row, col) = screen.cursor_position();
+                Some(format!("\x1b[?{};{}R", row + 1, col + 1).into_bytes())
+            }
+            // DA1 - primary device attributes
+            (None, 0, 'c') => Some(
+                format!("\x1b[?{}c", self.primary_device_attributes)
+                    .into_bytes(),
+            ),
+            // DA2 - secondary device attributes
+            (Some(b'>'), 0, 'c') => Some(
+                format!("\x1b[>{}c", self.secondary_device_attributes)
+                    .into_bytes(),
+            ),
+            // XTVERSION
+            (Some(b'>'), 0, 'q') => Some(
+                format!("\x1bP>|{}\x1b\\", self.name_and_version)
+                    .into_bytes(),
+            ),
+            // XTWINOPS - report text area size in characters
+            (None, 18, 't') => {
+                let (rows, cols) = screen.size();
+                Some(format!("\x1b[8;{rows};{cols}t").into_bytes())
+            }
+            _ => None,
+        }
+    }
+
+    // replies use the same terminator (BEL or ST) as the query, since some
+    // programs only recognize the one they sent
+    pub(crate) fn reply_osc(
+        &self,
+        params: &[&[u8]],
+        terminator: &str,
+    ) -> Option<Vec<u8>> {
+        let (cmd, args) = params.split_first()?;
+        let mut reply = vec![];
+        match *cmd {
+            // dynamic colors - each additional parameter refers to the next
+            // color in the sequence, so `OSC 10 ; ? ; ?` queries both the
+            // foreground and background colors
+            b"10" | b"11" | b"12" => {
+                let dynamic = [self.foreground, self.background, self.cursor];
+                let start = usize::from(cmd[1] - b'0');
+                for (i, arg) in args.iter().enumerate() {
+                    let Some(color) = dynamic.get(start + i) else {
+                        break;
+                    };
+                    if *arg == b"?" {
+                        reply.extend(
+                            format!(
+                                "\x1b]{};{}{}",
+                                10 + start + i,
+                                format_color(*color),
+                                terminator
+                            )
+                            .into_bytes(),
+                        );
+                    }
+                }
+            }
+            // palette colors
+            b"4" => {
+                for pair in args.chunks(2) {
+                    if let [idx, b"?"] = pair {
+                        let Some(idx) = std::str::from_utf8(idx)
+                            .ok()
+                            .and_then(|idx| idx.parse::<usize>().ok())
+                        else {
+                            continue;
+                        };
+                        if let Some(color) = self.palette.get(idx) {
+                            reply.extend(
+                                format!(
+                                    "\x1b]4;{};{}{}",
+                                    idx,
+                                    format_color(*color),
+                                    terminator
+                                )
+                                .into_bytes(),
+                            );
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+        if reply.is_empty() {
+            None
+        } else {
+            Some(reply)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn replies(data: &[u8]) -> Vec<u8> {
+        let mut term = crate::term::Terminal::new();
+        term.set_profile(Some(TerminalProfile::default()));
+        term.process(data);
+        term.take_replies()
+    }
+
+    #[test]
+    fn test_csi() {
+        assert_eq!(replies(b"\x1b[5n"), b"\x1b[0n");
+        assert_eq!(replies(b"\r\nfoo\x1b[6n"), b"\x1b[2;4R");
+        assert_eq!(replies(b"\x1b[3;5H\x1b[?6n"), b"\x1b[?3;5R");
+        assert_eq!(replies(b"\x1b[>c"), b"\x1b[>1;10;0c");
+        assert_eq!(replies(b"\x1b[18t"), b"\x1b[8;24;80t");
+        assert_eq!(replies(b"\x1b[31m\x1b[J"), b"");
+    }
+
+    #[test]
+    fn test_osc() {
+        assert_eq!(
+            replies(b"\x1b]11;?\x07"),
+            &b"\x1b]11;rgb:0000/0000/0000\x07"[..]
+        );
+        assert_eq!(
+            replies(b"\x1b]10;?;?\x1b\\"),
+            &b"\x1b]10;rgb:e5e5/e5e5/e5e5\x1b\\\x1b]11;rgb:0000/0000/0000\x1b\\"[..]
+        );
+        assert_eq!(
+            replies(b"\x1b]4;1;?;196;?;300;?\x07"),
+            &b"\x1b]4;1;rgb:cdcd/0000/0000\x07\x1b]4;196;rgb:ffff/0000/0000\x07"[..]
+        );
+        assert_eq!(replies(b"\x1b]4;1;rgb:00/00/00\x07"), b"");
+        assert_eq!(default_palette().len(), 256);
+        assert_eq!(default_palette()[255], (0xee, 0xee, 0xee));
+    }
+}
@@ -1,41 +1,153 @@
 use futures::future::Future as _;
 use futures::stream::Stream as _;
 use snafu::futures01::StreamExt as _;
+use snafu::ResultExt as _;
 
 /// A wrapper around `Process` which listens for terminal resize signals and
 /// propagates the changes into the process running on the pty.
 ///
 /// This is useful for running subprocesses in a pty that will ultimately be
-/// displayed in the user's terminal directly.
+/// displayed in the user's terminal directly. The size of the user's
+/// terminal in pixels is also propagated, if the terminal reports it.
+/// Sizes can also come from another source instead, via `with_sizes`.
+#[must_use = "streams do nothing unless polled"]
 pub struct ResizingProcess<R: tokio::io::AsyncRead + 'static> {
     process: crate::process::Process<R>,
     resizer: Box<
         dyn futures::stream::Stream<
-                Item = (u16, u16),
+                Item = ((u16, u16), (u16, u16)),
                 Error = crate::error::Error,
             > + Send,
     >,
+    resizer_done: bool,
+    debounce: std::time::Duration,
+    timer: Option<tokio::timer::Delay>,
+    pending: Option<((u16, u16), (u16, u16))>,
+    event_time: Option<std::time::Instant>,
 }
 
 impl<R: tokio::io::AsyncRead + 'static> ResizingProcess<R> {
     /// Creates a new `ResizingProcess` as a wrapper around the given
     /// `Process` instance.
     pub fn new(process: crate::process::Process<R>) -> Self {
-        Self {
+        Self::with_resizer(
             process,
-            resizer: Box::new(
+            Box::new(
                 tokio_terminal_resize::resizes()
                     .flatten_stream()
-                    .context(crate::error::Resize),
+                    .context(crate::error::Resize)
+                    .map(|size| (size, host_pixel_size())),
+            ),
+        )
+    }
+
+    /// Creates a new `ResizingProcess` as a wrapper around the given
+    /// `Process` instance, which takes terminal sizes (as `(rows, cols)`)
+    /// from `sizes` rather than from the user's terminal.
+    ///
+    /// This is useful when the pty is displayed somewhere other than the
+    /// terminal running this program, such as in a web client or in one
+    /// pane of a larger layout. The size of the pty in pixels is left
+    /// unknown. If `sizes` ends, the pty keeps the last size it received.
+    pub fn with_sizes<S>(
+        process: crate::process::Process<R>,
+        sizes: S,
+    ) -> Self
+    where
+        S: futures::stream::Stream<Item = (u16, u16)> + Send + 'static,
+        S::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        Self::with_resizer(
+            process,
+            Box::new(
+                sizes
+                    .map_err(|e| crate::error::Error::ResizeSource {
+                        source: e.into(),
+                    })
+                    .map(|size| (size, (0, 0))),
             ),
+        )
+    }
+
+    fn with_resizer(
+        process: crate::process::Process<R>,
+        resizer: Box<
+            dyn futures::stream::Stream<
+                    Item = ((u16, u16), (u16, u16)),
+                    Error = crate::error::Error,
+                > + Send,
+        >,
+    ) -> Self {
+        Self {
+            process,
+            resizer,
+            resizer_done: false,
+            debounce: std::time::Duration::from_secs(0),
+            timer: None,
+            pending: None,
+            event_time: None,
         }
     }
 
+    /// Waits until no new sizes have been received for `delay` before
+    /// resizing the pty, so that a burst of size changes (for instance
+    /// while the user is dragging the edge of their window) only resizes
+    /// the pty once, to the final size. `Event::Resize` is only emitted for
+    /// sizes which are actually applied.
+    ///
+    /// By default, sizes which are received at the same time are combined,
+    /// but no extra delay is added.
+    pub fn set_debounce(&mut self, delay: std::time::Duration) {
+        self.debounce = delay;
+    }
+
     /// Returns a mutable reference to the input object provided in the inner
     /// `Process` instance's constructor.
     pub fn input(&mut self) -> &mut R {
         self.process.input()
     }
+
+    /// Adds a sink which will receive a copy of all output read from the
+    /// pty. See `Process::tee` for details.
+    pub fn tee<W: tokio::io::AsyncWrite + Send + 'static>(
+        &mut self,
+        sink: W,
+    ) {
+        self.process.tee(sink);
+    }
+
+    /// Starts retaining the most recent output of the process. See
+    /// `Process::keep_transcript` for details.
+    pub fn keep_transcript(
+        &mut self,
+        limit: crate::transcript::TranscriptLimit,
+    ) {
+        self.process.keep_transcript(limit);
+    }
+
+    /// Returns the transcript of recent output, if `keep_transcript` has
+    /// been called.
+    pub fn transcript(&self) -> Option<&crate::transcript::Transcript> {
+        self.process.transcript()
+    }
+
+    /// Returns the time at which the most recently returned event was
+    /// observed. See `Process::last_event_time` for details. For
+    /// `Event::Resize` events, this is the time at which the pty was
+    /// resized.
+    pub fn last_event_time(&self) -> Option<std::time::Instant> {
+        self.event_time
+    }
+
+    /// Returns a reference to the inner `Process` instance.
+    pub fn process(&self) -> &crate::process::Process<R> {
+        &self.process
+    }
+
+    /// Returns a mutable reference to the inner `Process` instance.
+    pub fn process_mut(&mut self) -> &mut crate::process::Process<R> {
+        &mut self.process
+    }
 }
 
 impl<R: tokio::io::AsyncRead + 'static> ResizingProcess<R> {
@@ -54,11 +166,53 @@ impl<R: tokio::io::AsyncRead + 'static> ResizingProcess<R> {
         Option<crate::process::Event>,
         crate::error::Error,
     > {
-        let (rows, cols) =
-            component_future::try_ready!(self.resizer.poll()).unwrap();
-        self.process.resize(rows, cols);
+        let mut not_ready = false;
+        while !self.resizer_done {
+            match self.resizer.poll()? {
+                futures::Async::Ready(Some(size)) => {
+                    self.pending = Some(size);
+                    if self.debounce > std::time::Duration::from_secs(0) {
+                        self.timer = Some(tokio::timer::Delay::new(
+                            std::time::Instant::now() + self.debounce,
+                        ));
+                    }
+                }
+                futures::Async::Ready(None) => {
+                    log::debug!("resizer finished");
+                    self.resizer_done = true;
+                }
+                futures::Async::NotReady => {
+                    not_ready = true;
+                    break;
+                }
+            }
+        }
+
+        if self.pending.is_none() {
+            return if not_ready {
+                Ok(component_future::Async::NotReady)
+            } else {
+                Ok(component_future::Async::NothingToDo)
+            };
+        }
+        if let Some(timer) = &mut self.timer {
+            component_future::try_ready!(timer
+                .poll()
+                .context(crate::error::Timer));
+            self.timer = None;
+        }
+
+        let Some(((rows, cols), (width, height))) = self.pending.take()
+        else {
+            return Ok(component_future::Async::NothingToDo);
+        };
+        self.process.resize_with_pixels(rows, cols, width, height);
+        self.event_time = Some(std::time::Instant::now());
         Ok(component_future::Async::Ready(Some(
-            crate::process::Event::Resize { size: (rows, cols) },
+            crate::process::Event::Resize {
+                size: (rows, cols),
+                pixel_size: (width, height),
+            },
         )))
     }
 
@@ -68,13 +222,12 @@ impl<R: tokio::io::AsyncRead + 'static> ResizingProcess<R> {
         Option<crate::process::Event>,
         crate::error::Error,
     > {
-        Ok(component_future::Async::Ready(
-            component_future::try_ready!(self.process.poll()),
-        ))
+        let event = component_future::try_ready!(self.process.poll());
+        self.event_time = self.process.last_event_time();
+        Ok(component_future::Async::Ready(event))
     }
 }
 
-#[must_use = "streams do nothing unless polled"]
 impl<R: tokio::io::AsyncRead + 'static> futures::stream::Stream
     for ResizingProcess<R>
 {
@@ -85,3 +238,78 @@ impl<R: tokio::io::AsyncRead + 'static> futures::stream::Stream
         component_future::poll_stream(self, Self::POLL_FNS)
     }
 }
+
+// sets the size of the pty, including the size in pixels (which
+// PtyMaster::resize always sets to zero)
+pub fn set_window_size(
+    fd: std::os::unix::io::RawFd,
+    (rows, cols): (u16, u16),
+    (width, height): (u16, u16),
+) -> std::io::Result<()> {
+    let size = libc::winsize {
+        ws_row: rows,
+        ws_col: cols,
+        ws_xpixel: width,
+        ws_ypixel: height,
+    };
+    // safe because TIOCSWINSZ only reads the winsize struct we pass
+    if unsafe { libc::ioctl(fd, libc::TIOCSWINSZ, std::ptr::addr_of!(size)) }
+        != 0
+    {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+// returns ((rows, cols), (width, height)) for the terminal attached to fd
+pub fn window_size(
+    fd: std::os::unix::io::RawFd,
+) -> std::io::Result<((u16, u16), (u16, u16))> {
+    let mut size = std::mem::MaybeUninit::<libc::winsize>::uninit();
+    // safe because TIOCGWINSZ fully initializes size when it succeeds
+    let size = unsafe {
+        if libc::ioctl(fd, libc::TIOCGWINSZ, size.as_mut_ptr()) != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        size.assume_init()
+    };
+    Ok(((size.ws_row, size.ws_col), (size.ws_xpixel, size.ws_ypixel)))
+}
+
+// many terminals don't report their size in pixels, in which case this
+// returns (0, 0), the same as the kernel does
+fn host_pixel_size() -> (u16, u16) {
+    [libc::STDOUT_FILENO, libc::STDIN_FILENO, libc::STDERR_FILENO]
+        .iter()
+        .find_map(|fd| window_size(*fd).ok())
+        .map_or((0, 0), |(_, pixel_size)| pixel_size)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_with_sizes() {
+        let process = crate::process::test::sh("sleep 1; stty size");
+        let sizes = futures::stream::iter_ok::<_, std::io::Error>(vec![
+            (10, 20),
+            (24, 80),
+        ]);
+        let mut process = ResizingProcess::with_sizes(process, sizes);
+        process.set_debounce(std::time::Duration::from_millis(100));
+        let events = crate::process::test::run(process);
+
+        let resizes: Vec<_> = events
+            .iter()
+            .filter_map(|event| match event {
+                crate::process::Event::Resize { size, pixel_size } => {
+                    Some((*size, *pixel_size))
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(resizes, vec![((24, 80), (0, 0))]);
+        assert_eq!(crate::process::test::output(&events), b"24 80\r\n");
+    }
+}
@@ -0,0 +1,252 @@
+use futures::stream::Stream as _;
+
+/// A wrapper around `Process` which maintains an in-memory model of the
+/// terminal screen that the process is drawing to.
+///
+/// All output from the process is fed through a terminal parser (provided by
+/// the `vt100` crate), which tracks the contents and attributes of each cell,
+/// the cursor, the alternate screen, scroll regions, and so on. This wrapper
+/// keeps the size of that screen in sync with the pty, retains scrollback,
+/// and reports when the screen changes. Each chunk of output read from the
+/// pty is followed by an `Event::ScreenUpdated` event once it has been
+/// applied to the screen (after the last `Event::Output` event, if the chunk
+/// was split around other events), at which point the new screen contents
+/// can be inspected via `screen`. If `emit_diffs` is enabled, this
+/// is additionally followed by an `Event::ScreenDiff` event describing which
+/// cells changed.
+#[must_use = "streams do nothing unless polled"]
+pub struct ScreenProcess<R: tokio::io::AsyncRead + 'static> {
+    process: crate::process::Process<R>,
+    pending_events: std::collections::VecDeque<crate::process::Event>,
+    emit_diffs: bool,
+    prev_screen: Option<crate::diff::Snapshot>,
+}
+
+impl<R: tokio::io::AsyncRead + 'static> ScreenProcess<R> {
+    /// Creates a new `ScreenProcess` as a wrapper around the given `Process`
+    /// instance.
+    ///
+    /// The pty will be resized to the given size, and up to `scrollback_len`
+    /// rows which scroll off of the top of the screen will be retained.
+    pub fn new(
+        mut process: crate::process::Process<R>,
+        rows: u16,
+        cols: u16,
+        scrollback_len: usize,
+    ) -> Self {
+        process.set_scrollback(scrollback_len);
+        process.resize(rows, cols);
+        Self {
+            process,
+            pending_events: std::collections::VecDeque::new(),
+            emit_diffs: false,
+            prev_screen: None,
+        }
+    }
+
+    /// Returns a mutable reference to the input object provided in the inner
+    /// `Process` instance's constructor.
+    pub fn input(&mut self) -> &mut R {
+        self.process.input()
+    }
+
+    /// Requests a change to the pty's terminal size, and resizes the screen
+    /// to match.
+    ///
+    /// The pty resize will only be applied on the next call to `poll`.
+    pub fn resize(&mut self, rows: u16, cols: u16) {
+        self.process.resize(rows, cols);
+    }
+
+    /// Returns the current state of the terminal screen.
+    // new() enables terminal state tracking on the inner process, and there
+    // is no way to disable it again, so the screen is always available
+    #[allow(clippy::missing_panics_doc)]
+    pub fn screen(&self) -> &vt100::Screen {
+        self.process.screen().unwrap()
+    }
+
+    /// Returns a mutable reference to the current state of the terminal
+    /// screen.
+    ///
+    /// This is mostly useful for scrolling through the scrollback via
+    /// `vt100::Screen::set_scrollback`.
+    pub fn screen_mut(&mut self) -> &mut vt100::Screen {
+        self.process.screen_mut()
+    }
+
+    /// Enables or disables `Event::ScreenDiff` events.
+    ///
+    /// When enabled, each `Event::ScreenUpdated` event is followed by an
+    /// `Event::ScreenDiff` event containing the cells which changed since the
+    /// previous `Event::ScreenDiff` event (or the entire screen, for the
+    /// first one and after the screen is resized).
+    pub fn emit_diffs(&mut self, enabled: bool) {
+        self.emit_diffs = enabled;
+        if !enabled {
+            self.prev_screen = None;
+        }
+    }
+
+    /// Returns a reference to the inner `Process` instance.
+    pub fn process(&self) -> &crate::process::Process<R> {
+        &self.process
+    }
+
+    /// Returns a mutable reference to the inner `Process` instance.
+    pub fn process_mut(&mut self) -> &mut crate::process::Process<R> {
+        &mut self.process
+    }
+}
+
+impl<R: tokio::io::AsyncRead + 'static> ScreenProcess<R> {
+    const POLL_FNS:
+        &'static [&'static dyn for<'a> Fn(
+            &'a mut Self,
+        )
+            -> component_future::Poll<
+            Option<crate::process::Event>,
+            crate::error::Error,
+        >] = &[&Self::poll_pending_events, &Self::poll_process];
+
+    // the signature is required to match the rest of POLL_FNS
+    #[allow(clippy::unnecessary_wraps)]
+    fn poll_pending_events(
+        &mut self,
+    ) -> component_future::Poll<
+        Option<crate::process::Event>,
+        crate::error::Error,
+    > {
+        Ok(self
+            .pending_events
+            .pop_front()
+            .map_or(component_future::Async::NothingToDo, |event| {
+                component_future::Async::Ready(Some(event))
+            }))
+    }
+
+    fn poll_process(
+        &mut self,
+    ) -> component_future::Poll<
+        Option<crate::process::Event>,
+        crate::error::Error,
+    > {
+        let event = component_future::try_ready!(self.process.poll());
+        // the screen is updated with an entire chunk of output at once, even
+        // if it is returned as several output events (split around the
+        // events it generated), so only report the update after the last one
+        if let (Some(crate::process::Event::Output { .. }), false) =
+            (&event, self.process.has_pending_output())
+        {
+            self.pending_events
+                .push_back(crate::process::Event::ScreenUpdated);
+            if self.emit_diffs {
+                let screen = self.screen();
+                let diff = crate::diff::ScreenDiff::from_snapshot(
+                    self.prev_screen.as_ref(),
+                    screen,
+                );
+                let snapshot = crate::diff::Snapshot::new(screen);
+                self.prev_screen = Some(snapshot);
+                self.pending_events
+                    .push_back(crate::process::Event::ScreenDiff { diff });
+            }
+        }
+        Ok(component_future::Async::Ready(event))
+    }
+}
+
+impl<R: tokio::io::AsyncRead + 'static> futures::stream::Stream
+    for ScreenProcess<R>
+{
+    type Item = crate::process::Event;
+    type Error = crate::error::Error;
+
+    fn poll(&mut self) -> futures::Poll<Option<Self::Item>, Self::Error> {
+        component_future::poll_stream(self, Self::POLL_FNS)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_screen() {
+        let buf = std::io::Cursor::new(b"\x1b[31mhello\x1b[m\n");
+        let process = crate::process::Process::new("cat", &[], buf);
+        let mut screen = ScreenProcess::new(process, 24, 80, 0);
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+
+        let mut saw_update = false;
+        loop {
+            let (event, rest) = runtime
+                .block_on(screen.into_future())
+                .map_err(|(e, _)| e)
+                .unwrap();
+            screen = rest;
+            match event {
+                Some(crate::process::Event::ScreenUpdated) => {
+                    saw_update = true;
+                }
+                Some(_) => {}
+                None => break,
+            }
+        }
+
+        assert!(saw_update);
+        assert_eq!(screen.screen().size(), (24, 80));
+        // the first line is the pty echoing the input back
+        assert_eq!(screen.screen().contents(), "^[[31mhello^[[m\nhello");
+        assert_eq!(
+            screen.screen().cell(0, 0).unwrap().fgcolor(),
+            vt100::Color::Default
+        );
+        assert_eq!(
+            screen.screen().cell(1, 0).unwrap().fgcolor(),
+            vt100::Color::Idx(1)
+        );
+        assert_eq!(screen.screen().cursor_position(), (2, 0));
+    }
+
+    #[test]
+    fn test_diffs() {
+        let buf = std::io::Cursor::new(b"hello\n");
+        let process = crate::process::Process::new("cat", &[], buf);
+        let mut screen = ScreenProcess::new(process, 4, 20, 100);
+        screen.emit_diffs(true);
+        let events = crate::process::test::run(screen);
+
+        let mut diffs = vec![];
+        let mut prev = None;
+        for event in events {
+            if let crate::process::Event::ScreenDiff { diff } = event {
+                // diffs always directly follow the screen update
+                assert_eq!(prev, Some(crate::process::Event::ScreenUpdated));
+                diffs.push(diff);
+                prev = None;
+            } else {
+                prev = Some(event);
+            }
+        }
+        assert!(!diffs.is_empty());
+        // the first diff contains the whole screen
+        assert_eq!(diffs[0].size, (4, 20));
+        assert_eq!(diffs[0].rows.len(), 4);
+
+        // applying each diff in turn reproduces the final screen
+        let mut rows = vec![vec![String::new(); 20]; 4];
+        for diff in &diffs {
+            for row in &diff.rows {
+                for (col, cell) in &row.cells {
+                    rows[usize::from(row.row)][usize::from(*col)] =
+                        cell.contents.clone();
+                }
+            }
+        }
+        let text: Vec<String> = rows.iter().map(|row| row.concat()).collect();
+        assert_eq!(text[0], "hello");
+        assert_eq!(text[1], "hello");
+        assert_eq!(diffs.last().unwrap().cursor_position, (2, 0));
+    }
+}
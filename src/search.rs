@@ -0,0 +1,356 @@
+/// A position on the screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Position {
+    /// The row, where 0 is the top row of the visible screen. Rows in the
+    /// scrollback have negative indexes, with -1 being the row immediately
+    /// above the top of the visible screen.
+    pub row: i32,
+
+    /// The (0-based) column.
+    pub col: u16,
+}
+
+/// A match found by `ScreenText::find` or `ScreenText::find_regex`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Match {
+    /// The position of the first character of the match.
+    pub start: Position,
+
+    /// The position immediately after the last character of the match. If
+    /// the match was wrapped across multiple rows, this will be on a
+    /// different row from `start`.
+    pub end: Position,
+
+    /// The matched text.
+    pub text: String,
+}
+
+struct Row {
+    index: i32,
+    // one entry per column, with an empty string for the second half of
+    // wide characters
+    cells: Vec<String>,
+    wrapped: bool,
+}
+
+/// A snapshot of the text on a terminal screen, which can be searched and
+/// extracted.
+///
+/// Rows which were wrapped by the terminal (because the text was too long
+/// to fit on one row, rather than because a newline was printed) are
+/// treated as a single logical line, so searches will find text which
+/// crosses a wrapped row boundary.
+pub struct ScreenText {
+    rows: Vec<Row>,
+}
+
+impl ScreenText {
+    /// Creates a snapshot of the visible rows of `screen`.
+    ///
+    /// This ignores the scrollback position set via
+    /// `vt100::Screen::set_scrollback`, and always uses the current contents
+    /// of the screen.
+    #[must_use]
+    pub fn new(screen: &vt100::Screen) -> Self {
+        Self::read(screen, false)
+    }
+
+    /// Creates a snapshot of the scrollback and visible rows of `screen`.
+    #[must_use]
+    pub fn with_scrollback(screen: &vt100::Screen) -> Self {
+        Self::read(screen, true)
+    }
+
+    fn read(screen: &vt100::Screen, scrollback: bool) -> Self {
+        let mut rows = vec![];
+        for_each_row(screen, scrollback, |screen, row, index| {
+            rows.push(read_row(screen, row, index));
+        });
+        Self { rows }
+    }
+
+    /// Returns the number of rows of scrollback included in this snapshot.
+    #[must_use]
+    pub fn scrollback_rows(&self) -> usize {
+        self.rows.iter().filter(|row| row.index < 0).count()
+    }
+
+    /// Finds all occurrences of `needle`.
+    ///
+    /// Each logical line is searched separately, so matches will never
+    /// include a newline.
+    #[must_use]
+    pub fn find(&self, needle: &str) -> Vec<Match> {
+        if needle.is_empty() {
+            return vec![];
+        }
+        self.find_matches(|text| {
+            text.match_indices(needle)
+                .map(|(start, m)| (start, start + m.len()))
+                .collect()
+        })
+    }
+
+    /// Finds all matches of `re`.
+    ///
+    /// Each logical line is searched separately, so matches will never
+    /// include a newline. Empty matches are ignored.
+    #[must_use]
+    pub fn find_regex(&self, re: &regex::Regex) -> Vec<Match> {
+        self.find_matches(|text| {
+            re.find_iter(text)
+                .filter(|m| m.start() != m.end())
+                .map(|m| (m.start(), m.end()))
+                .collect()
+        })
+    }
+
+    fn find_matches(
+        &self,
+        f: impl Fn(&str) -> Vec<(usize, usize)>,
+    ) -> Vec<Match> {
+        let mut matches = vec![];
+        for line in self.logical_lines() {
+            let (text, positions) = self.line_text(line);
+            // a cell can contain multiple characters (with combining
+            // characters), so look for the cell containing each offset
+            let cell_at = |offset| {
+                positions
+                    [positions.partition_point(|(o, _, _)| *o <= offset) - 1]
+            };
+            for (start, end) in f(&text) {
+                let (_, start_pos, _) = cell_at(start);
+                let (_, last, width) = cell_at(end - 1);
+                matches.push(Match {
+                    start: start_pos,
+                    end: Position {
+                        row: last.row,
+                        col: last.col + width,
+                    },
+                    text: text[start..end].to_string(),
+                });
+            }
+        }
+        matches
+    }
+
+    /// Returns the text of each logical line, with trailing whitespace
+    /// removed.
+    #[must_use]
+    pub fn lines(&self) -> Vec<String> {
+        self.logical_lines()
+            .map(|line| self.line_text(line).0)
+            .collect()
+    }
+
+    /// Returns the text of the logical line which contains `row`, with
+    /// trailing whitespace removed.
+    #[must_use]
+    pub fn line_at(&self, row: i32) -> Option<String> {
+        self.logical_lines()
+            .find(|line| {
+                self.rows[line.clone()].iter().any(|r| r.index == row)
+            })
+            .map(|line| self.line_text(line).0)
+    }
+
+    /// Returns the text in the rectangle with the top left corner at `start`
+    /// and the bottom right corner immediately before `end` (so `end` itself
+    /// is not included), with each row separated by a newline. Trailing
+    /// whitespace is removed from each row.
+    pub fn region(&self, start: Position, end: Position) -> String {
+        let mut region = vec![];
+        for row in &self.rows {
+            if row.index < start.row || row.index >= end.row {
+                continue;
+            }
+            let text: String = row
+                .cells
+                .iter()
+                .skip(usize::from(start.col))
+                .take(usize::from(end.col.saturating_sub(start.col)))
+                .map(String::as_str)
+                .collect();
+            region.push(text.trim_end().to_string());
+        }
+        region.join("\n")
+    }
+
+    fn logical_lines(
+        &self,
+    ) -> impl Iterator<Item = std::ops::Range<usize>> + '_ {
+        let mut start = 0;
+        (0..self.rows.len()).filter_map(move |i| {
+            if self.rows[i].wrapped && i + 1 < self.rows.len() {
+                None
+            } else {
+                let line = start..i + 1;
+                start = i + 1;
+                Some(line)
+            }
+        })
+    }
+
+    // returns the text of the line, along with the byte offset, position,
+    // and width of each character
+    fn line_text(
+        &self,
+        line: std::ops::Range<usize>,
+    ) -> (String, Vec<(usize, Position, u16)>) {
+        let mut text = String::new();
+        let mut positions = vec![];
+        for row in &self.rows[line] {
+            let mut cells = row.cells.iter().enumerate().peekable();
+            while let Some((col, cell)) = cells.next() {
+                if cell.is_empty() {
+                    continue;
+                }
+                // wide characters are followed by an empty continuation cell
+                let width = if cells.peek().is_some_and(|(_, c)| c.is_empty())
+                {
+                    2
+                } else {
+                    1
+                };
+                // the column index always fits since it came from a u16
+                #[allow(clippy::cast_possible_truncation)]
+                let position = Position {
+                    row: row.index,
+                    col: col as u16,
+                };
+                positions.push((text.len(), position, width));
+                text.push_str(cell);
+            }
+        }
+        let len = text.trim_end().len();
+        text.truncate(len);
+        positions.retain(|(offset, _, _)| *offset < len);
+        (text, positions)
+    }
+}
+
+// calls f with each row of the screen (and optionally the scrollback), in
+// order from top to bottom. f is passed a view of the screen along with the
+// row to read from that view, and the index of the row as described in
+// Position::row.
+pub fn for_each_row(
+    screen: &vt100::Screen,
+    scrollback: bool,
+    mut f: impl FnMut(&vt100::Screen, u16, i32),
+) {
+    // the visible rows can be read directly, unless the screen has been
+    // scrolled back and so has to be scrolled to the bottom first
+    if !scrollback && screen.scrollback() == 0 {
+        let (rows, _) = screen.size();
+        for row in 0..rows {
+            f(screen, row, i32::from(row));
+        }
+        return;
+    }
+
+    let mut screen = screen.clone();
+    screen.set_scrollback(if scrollback { usize::MAX } else { 0 });
+    let scrollback_len = screen.scrollback();
+    let (rows, _) = screen.size();
+    let total = scrollback_len + usize::from(rows);
+
+    // vt100 only exposes the rows which are currently visible, so we have
+    // to scroll through the history a page at a time
+    let mut line = 0;
+    while line < total {
+        let offset = scrollback_len.saturating_sub(line);
+        screen.set_scrollback(offset);
+        let base = scrollback_len - offset;
+        for row in (line - base)..usize::from(rows) {
+            // row is always less than the screen height, and the scrollback
+            // will never be anywhere near i32::MAX rows
+            #[allow(
+                clippy::cast_possible_truncation,
+                clippy::cast_possible_wrap
+            )]
+            f(
+                &screen,
+                row as u16,
+                (base + row) as i32 - scrollback_len as i32,
+            );
+            line += 1;
+        }
+    }
+}
+
+fn read_row(screen: &vt100::Screen, row: u16, label: i32) -> Row {
+    let (_, cols) = screen.size();
+    let cells = (0..cols)
+        .map(|col| match screen.cell(row, col) {
+            Some(cell) if cell.is_wide_continuation() => String::new(),
+            Some(cell) if cell.has_contents() => cell.contents().to_string(),
+            _ => " ".to_string(),
+        })
+        .collect();
+    Row {
+        index: label,
+        cells,
+        wrapped: screen.row_wrapped(row),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_find() {
+        let mut parser = vt100::Parser::new(3, 10, 10);
+        parser
+            .process(b"first\r\nsecond\r\n[ OK ] [Cancel]\r\n\xe4\xb8\xadOK");
+        let text = ScreenText::new(parser.screen());
+        assert_eq!(text.scrollback_rows(), 0);
+        assert_eq!(
+            text.find("OK"),
+            vec![
+                Match {
+                    start: Position { row: 0, col: 2 },
+                    end: Position { row: 0, col: 4 },
+                    text: "OK".to_string(),
+                },
+                Match {
+                    start: Position { row: 2, col: 2 },
+                    end: Position { row: 2, col: 4 },
+                    text: "OK".to_string(),
+                },
+            ]
+        );
+        // matches across the wrapped row boundary
+        let m = text.find_regex(&regex::Regex::new(r"\[C\w+\]").unwrap());
+        assert_eq!(m.len(), 1);
+        assert_eq!(m[0].start, Position { row: 0, col: 7 });
+        assert_eq!(m[0].end, Position { row: 1, col: 5 });
+        assert_eq!(
+            text.lines(),
+            vec!["[ OK ] [Cancel]".to_string(), "\u{4e2d}OK".to_string()]
+        );
+        assert_eq!(text.line_at(1).unwrap(), "[ OK ] [Cancel]");
+        assert_eq!(
+            text.region(
+                Position { row: 0, col: 0 },
+                Position { row: 2, col: 3 }
+            ),
+            "[ O\nnce"
+        );
+
+        let text = ScreenText::with_scrollback(parser.screen());
+        assert_eq!(text.scrollback_rows(), 2);
+        assert_eq!(
+            text.find("second")[0].start,
+            Position { row: -1, col: 0 }
+        );
+        assert_eq!(text.line_at(-2).unwrap(), "first");
+        assert_eq!(text.lines().len(), 4);
+
+        // the scrollback position of the screen itself is ignored
+        parser.screen_mut().set_scrollback(1);
+        let text = ScreenText::new(parser.screen());
+        assert_eq!(text.scrollback_rows(), 0);
+        assert_eq!(text.line_at(0).unwrap(), "[ OK ] [Cancel]");
+    }
+}
@@ -0,0 +1,103 @@
+use std::os::unix::fs::DirBuilderExt as _;
+
+const BASHRC: &str = include_str!("shell/bashrc");
+const ZSHENV: &str = include_str!("shell/zshenv");
+
+static COUNTER: std::sync::atomic::AtomicUsize =
+    std::sync::atomic::AtomicUsize::new(0);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Shell {
+    Bash,
+    Zsh,
+}
+
+impl Shell {
+    fn detect(cmd: &str) -> Option<Self> {
+        match std::path::Path::new(cmd).file_name()?.to_str()? {
+            "bash" => Some(Self::Bash),
+            "zsh" => Some(Self::Zsh),
+            _ => None,
+        }
+    }
+}
+
+// holds the temporary directory containing the integration scripts, which
+// needs to stay around until the shell has finished starting up. it is
+// removed when this is dropped.
+pub struct ShellIntegration {
+    shell: Shell,
+    dir: std::path::PathBuf,
+}
+
+impl ShellIntegration {
+    // returns Ok(None) if cmd isn't a shell we know how to integrate with
+    pub fn new(cmd: &str) -> std::io::Result<Option<Self>> {
+        let Some(shell) = Shell::detect(cmd) else {
+            return Ok(None);
+        };
+
+        let dir = std::env::temp_dir().join(format!(
+            "{}-{}-{}",
+            env!("CARGO_PKG_NAME"),
+            std::process::id(),
+            COUNTER.fetch_add(1, std::sync::atomic::Ordering::SeqCst)
+        ));
+        std::fs::DirBuilder::new().mode(0o700).create(&dir)?;
+        // construct this before writing the scripts so that the directory
+        // is cleaned up on failure
+        let integration = Self { shell, dir };
+        match shell {
+            Shell::Bash => {
+                std::fs::write(integration.dir.join("bashrc"), BASHRC)?;
+            }
+            Shell::Zsh => {
+                std::fs::write(integration.dir.join(".zshenv"), ZSHENV)?;
+            }
+        }
+        Ok(Some(integration))
+    }
+
+    // bash only reads --rcfile when it is given before any other arguments
+    pub fn args(&self) -> Vec<std::ffi::OsString> {
+        match self.shell {
+            Shell::Bash => {
+                vec!["--rcfile".into(), self.dir.join("bashrc").into()]
+            }
+            Shell::Zsh => vec![],
+        }
+    }
+
+    pub fn apply_env(&self, command: &mut std::process::Command) {
+        if self.shell == Shell::Zsh {
+            if let Some(zdotdir) = std::env::var_os("ZDOTDIR") {
+                command.env("__TPPS_ZDOTDIR", zdotdir);
+            }
+            command.env("ZDOTDIR", &self.dir);
+        }
+    }
+}
+
+impl Drop for ShellIntegration {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(Shell::detect("bash"), Some(Shell::Bash));
+        assert_eq!(Shell::detect("/usr/bin/zsh"), Some(Shell::Zsh));
+        assert_eq!(Shell::detect("fish"), None);
+
+        let integration = ShellIntegration::new("bash").unwrap().unwrap();
+        let dir = integration.dir.clone();
+        assert!(dir.join("bashrc").exists());
+        drop(integration);
+        assert!(!dir.exists());
+    }
+}
@@ -0,0 +1,209 @@
+/// Options controlling how `AnsiStripper` converts terminal output into plain
+/// text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+// each option can be enabled independently of the others
+#[allow(clippy::struct_excessive_bools)]
+pub struct StripOptions {
+    /// Whether tab characters should be kept in the output.
+    pub keep_tabs: bool,
+
+    /// Whether newline characters should be kept in the output. If this is
+    /// false, each newline is replaced by a single space instead, so that
+    /// words on adjacent lines don't run together.
+    pub keep_newlines: bool,
+
+    /// Whether backspace characters should move back over the previous
+    /// character in the current line (so that it will be overwritten by the
+    /// next printed character) rather than just being removed.
+    pub interpret_backspace: bool,
+
+    /// Whether carriage return characters should move back to the start of
+    /// the current line (so that it will be overwritten by subsequently
+    /// printed characters) rather than just being removed.
+    pub interpret_carriage_return: bool,
+}
+
+impl Default for StripOptions {
+    fn default() -> Self {
+        Self {
+            keep_tabs: true,
+            keep_newlines: true,
+            interpret_backspace: true,
+            interpret_carriage_return: true,
+        }
+    }
+}
+
+/// A streaming parser which removes escape sequences and control characters
+/// from terminal output, leaving plain text.
+///
+/// CSI, OSC, and DCS sequences (as well as any other escape sequences) are
+/// removed entirely, and control characters are handled according to the
+/// given `StripOptions`. Since the parser is stateful, escape sequences and
+/// multibyte characters which are split across multiple calls to `process`
+/// are handled correctly, so this can be fed the data from each
+/// `Event::Output` directly.
+///
+/// When backspace or carriage return interpretation is enabled, text is only
+/// returned once the line it is on is complete (since later output could
+/// still overwrite it). Use `flush` to retrieve the contents of a partial
+/// line, for instance once the process has exited.
+pub struct AnsiStripper {
+    parser: vte::Parser,
+    performer: Performer,
+}
+
+impl AnsiStripper {
+    /// Creates a new stripper with the given options.
+    #[must_use]
+    pub fn new(options: StripOptions) -> Self {
+        Self {
+            parser: vte::Parser::new(),
+            performer: Performer {
+                options,
+                output: String::new(),
+                line: vec![],
+                col: 0,
+            },
+        }
+    }
+
+    /// Processes a chunk of terminal output, returning any plain text which
+    /// is now complete.
+    pub fn process(&mut self, data: &[u8]) -> String {
+        self.parser.advance(&mut self.performer, data);
+        std::mem::take(&mut self.performer.output)
+    }
+
+    /// Returns the text of the current partial line, if any.
+    ///
+    /// This does not reset the parser state, so any escape sequence which is
+    /// still in progress will continue to be handled correctly by subsequent
+    /// calls to `process`.
+    pub fn flush(&mut self) -> String {
+        self.performer.finish_line();
+        std::mem::take(&mut self.performer.output)
+    }
+}
+
+impl Default for AnsiStripper {
+    fn default() -> Self {
+        Self::new(StripOptions::default())
+    }
+}
+
+struct Performer {
+    options: StripOptions,
+    output: String,
+    line: Vec<char>,
+    col: usize,
+}
+
+impl Performer {
+    fn buffers_lines(&self) -> bool {
+        self.options.interpret_backspace
+            || self.options.interpret_carriage_return
+    }
+
+    fn put_char(&mut self, c: char) {
+        if !self.buffers_lines() {
+            self.output.push(c);
+            return;
+        }
+
+        if self.col < self.line.len() {
+            self.line[self.col] = c;
+        } else {
+            self.line.push(c);
+        }
+        self.col += 1;
+    }
+
+    fn finish_line(&mut self) {
+        self.output.extend(self.line.drain(..));
+        self.col = 0;
+    }
+}
+
+impl vte::Perform for Performer {
+    fn print(&mut self, c: char) {
+        self.put_char(c);
+    }
+
+    fn execute(&mut self, byte: u8) {
+        match byte {
+            b'\n' => {
+                self.finish_line();
+                self.output.push(if self.options.keep_newlines {
+                    '\n'
+                } else {
+                    ' '
+                });
+            }
+            b'\t' if self.options.keep_tabs => {
+                self.put_char('\t');
+            }
+            b'\x08' if self.options.interpret_backspace => {
+                self.col = self.col.saturating_sub(1);
+            }
+            b'\r' if self.options.interpret_carriage_return => {
+                self.col = 0;
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_strip() {
+        let mut stripper = AnsiStripper::default();
+        assert_eq!(
+            stripper.process(b"\x1b[31mred\x1b[m \x1b]0;title\x07text\r\n"),
+            "red text\n"
+        );
+        assert_eq!(stripper.process(b"\x1bP1$r0m\x1b\\dcs\r\n"), "dcs\n");
+        assert_eq!(stripper.process(b"a\tb\x07\x0e\r\n"), "a\tb\n");
+    }
+
+    #[test]
+    fn test_split_sequences() {
+        let mut stripper = AnsiStripper::default();
+        assert_eq!(stripper.process(b"foo\x1b["), "");
+        assert_eq!(stripper.process(b"1;3"), "");
+        assert_eq!(stripper.process(b"1mbar\x1b]2;ti"), "");
+        assert_eq!(stripper.process(b"tle\x1b\\\xe2\x98"), "");
+        assert_eq!(stripper.process(b"\x83\n"), "foobar\u{2603}\n");
+    }
+
+    #[test]
+    fn test_overwrite() {
+        let mut stripper = AnsiStripper::default();
+        assert_eq!(stripper.process(b"abcd\x08\x08XY\n"), "abXY\n");
+        assert_eq!(stripper.process(b"50%\r100%\r\n"), "100%\n");
+        assert_eq!(stripper.process(b"prompt$ "), "");
+        assert_eq!(stripper.flush(), "prompt$ ");
+
+        let mut stripper = AnsiStripper::new(StripOptions {
+            keep_tabs: false,
+            keep_newlines: false,
+            interpret_backspace: false,
+            interpret_carriage_return: false,
+        });
+        assert_eq!(stripper.process(b"a\tb\x08c\r\nd"), "abc d");
+    }
+
+    #[test]
+    fn test_join_lines() {
+        let mut stripper = AnsiStripper::new(StripOptions {
+            keep_newlines: false,
+            ..StripOptions::default()
+        });
+        assert_eq!(stripper.process(b"abc\r\nd"), "abc ");
+        assert_eq!(stripper.process(b"ef\r\n\r\ng"), "def  ");
+        assert_eq!(stripper.flush(), "g");
+    }
+}
@@ -0,0 +1,295 @@
+use snafu::ResultExt as _;
+
+const DEFAULT_BUFFER_SIZE: usize = 64;
+
+/// Determines what happens when a subscriber is not keeping up with the
+/// events being generated by the process.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LagPolicy {
+    /// Events which don't fit in the subscriber's buffer are discarded for
+    /// that subscriber.
+    Drop,
+
+    /// No more output is read from the process until the subscriber has
+    /// caught up.
+    ///
+    /// Output is only paused between reads, so all of the events generated
+    /// by a single read from the process are still queued for the
+    /// subscriber, even if that takes it past its buffer size.
+    Block,
+
+    /// The subscriber is disconnected, and its stream will return a
+    /// `SubscriberLagged` error.
+    Disconnect,
+}
+
+/// Options for a new subscription created by `Process::subscribe`.
+pub struct SubscribeOptions {
+    /// The number of events which can be buffered for this subscriber
+    /// before the lag policy applies. This must be at least 1, and a value of
+    /// 0 is treated as 1.
+    pub buffer_size: usize,
+
+    /// What to do when the subscriber's buffer is full.
+    pub lag_policy: LagPolicy,
+
+    /// If set, only events for which this function returns true will be
+    /// sent to this subscriber.
+    pub filter: Option<Box<dyn Fn(&crate::process::Event) -> bool + Send>>,
+}
+
+impl Default for SubscribeOptions {
+    fn default() -> Self {
+        Self {
+            buffer_size: DEFAULT_BUFFER_SIZE,
+            lag_policy: LagPolicy::Block,
+            filter: None,
+        }
+    }
+}
+
+/// A stream of events for a single subscriber, created by
+/// `Process::subscribe`.
+///
+/// The stream ends when the process stream itself ends (or is dropped).
+#[must_use = "streams do nothing unless polled"]
+pub struct Subscription {
+    receiver: tokio::sync::mpsc::Receiver<crate::process::Event>,
+    lagged: std::sync::Arc<std::sync::atomic::AtomicBool>,
+}
+
+impl futures::stream::Stream for Subscription {
+    type Item = crate::process::Event;
+    type Error = crate::error::Error;
+
+    fn poll(&mut self) -> futures::Poll<Option<Self::Item>, Self::Error> {
+        let event = futures::try_ready!(self
+            .receiver
+            .poll()
+            .context(crate::error::Subscription));
+        if event.is_none()
+            && self.lagged.load(std::sync::atomic::Ordering::SeqCst)
+        {
+            return Err(crate::error::Error::SubscriberLagged);
+        }
+        Ok(futures::Async::Ready(event))
+    }
+}
+
+pub struct Subscriber {
+    sender: tokio::sync::mpsc::Sender<crate::process::Event>,
+    backlog: std::collections::VecDeque<crate::process::Event>,
+    // the number of events at the front of the backlog which are from the
+    // initial replay rather than live events
+    replay_len: usize,
+    buffer_size: usize,
+    lag_policy: LagPolicy,
+    filter: Option<Box<dyn Fn(&crate::process::Event) -> bool + Send>>,
+    lagged: std::sync::Arc<std::sync::atomic::AtomicBool>,
+}
+
+impl Subscriber {
+    pub fn new(
+        options: SubscribeOptions,
+        replay: Vec<crate::process::Event>,
+    ) -> (Self, Subscription) {
+        // tokio's channels panic when given a buffer size of 0
+        let buffer_size = options.buffer_size.max(1);
+        let (sender, receiver) = tokio::sync::mpsc::channel(buffer_size);
+        let lagged =
+            std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let mut subscriber = Self {
+            sender,
+            backlog: std::collections::VecDeque::new(),
+            replay_len: 0,
+            buffer_size,
+            lag_policy: options.lag_policy,
+            filter: options.filter,
+            lagged: lagged.clone(),
+        };
+        // the initial replay is always delivered in full, regardless of the
+        // lag policy
+        for event in replay {
+            if subscriber.wants(&event) {
+                subscriber.backlog.push_back(event);
+            }
+        }
+        subscriber.replay_len = subscriber.backlog.len();
+        (subscriber, Subscription { receiver, lagged })
+    }
+
+    pub fn blocks(&self) -> bool {
+        self.lag_policy == LagPolicy::Block && !self.backlog.is_empty()
+    }
+
+    // returns false if the subscriber should be removed
+    pub fn send(&mut self, event: &crate::process::Event) -> bool {
+        if !self.wants(event) {
+            return true;
+        }
+
+        if self.lag_policy == LagPolicy::Block {
+            self.backlog.push_back(event.clone());
+            return true;
+        }
+
+        // live events queued behind the initial replay are subject to the
+        // lag policy too, since otherwise a subscriber which stalls during
+        // the replay could make the backlog grow without bound
+        if !self.backlog.is_empty() {
+            if self.backlog.len() - self.replay_len >= self.buffer_size {
+                return self.lag();
+            }
+            self.backlog.push_back(event.clone());
+            return true;
+        }
+
+        match self.sender.try_send(event.clone()) {
+            Ok(()) => true,
+            Err(e) if e.is_full() => self.lag(),
+            Err(_) => false,
+        }
+    }
+
+    // called when the process stream ends. blocking subscribers have
+    // already been flushed at this point, but other subscribers may still
+    // have a backlog, which is delivered if there is room. if there isn't,
+    // the subscription ends with an error rather than appearing to end
+    // cleanly with events missing.
+    pub fn finish(&mut self) {
+        while let Some(event) = self.backlog.pop_front() {
+            if self.sender.try_send(event).is_err() {
+                log::debug!("subscriber lagged at end of stream");
+                self.lagged.store(true, std::sync::atomic::Ordering::SeqCst);
+                break;
+            }
+        }
+    }
+
+    // returns false if the subscriber should be removed
+    fn lag(&self) -> bool {
+        match self.lag_policy {
+            LagPolicy::Drop => {
+                log::debug!("subscriber lagged, dropping event");
+                true
+            }
+            LagPolicy::Disconnect => {
+                log::debug!("subscriber lagged, disconnecting");
+                self.lagged.store(true, std::sync::atomic::Ordering::SeqCst);
+                false
+            }
+            LagPolicy::Block => unreachable!(),
+        }
+    }
+
+    // returns Err(()) if the subscriber should be removed
+    pub fn poll_flush(&mut self) -> Result<component_future::Async<()>, ()> {
+        if self.backlog.is_empty() {
+            return Ok(component_future::Async::NothingToDo);
+        }
+
+        match self.sender.poll_ready() {
+            Ok(futures::Async::Ready(())) => {
+                let event = self.backlog.pop_front().unwrap();
+                self.replay_len = self.replay_len.saturating_sub(1);
+                self.sender.try_send(event).map_err(|_| ())?;
+                Ok(component_future::Async::DidWork)
+            }
+            Ok(futures::Async::NotReady) => {
+                Ok(component_future::Async::NotReady)
+            }
+            Err(_) => Err(()),
+        }
+    }
+
+    fn wants(&self, event: &crate::process::Event) -> bool {
+        let Some(filter) = &self.filter else {
+            return true;
+        };
+        filter(event)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use futures::stream::Stream as _;
+
+    #[test]
+    fn test_backlog_lag_policy() {
+        let replay = vec![
+            crate::process::Event::Output {
+                data: b"a".to_vec(),
+            },
+            crate::process::Event::Output {
+                data: b"b".to_vec(),
+            },
+        ];
+        let options = |lag_policy| SubscribeOptions {
+            buffer_size: 1,
+            lag_policy,
+            filter: None,
+        };
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+
+        let (mut subscriber, subscription) =
+            Subscriber::new(options(LagPolicy::Disconnect), replay.clone());
+        assert!(subscriber.send(&crate::process::Event::Bell));
+        assert!(!subscriber.send(&crate::process::Event::Bell));
+        drop(subscriber);
+        assert!(matches!(
+            runtime.block_on(subscription.collect()),
+            Err(crate::error::Error::SubscriberLagged)
+        ));
+
+        let (mut subscriber, subscription) =
+            Subscriber::new(options(LagPolicy::Drop), replay.clone());
+        assert!(subscriber.send(&crate::process::Event::Bell));
+        assert!(subscriber.send(&crate::process::Event::Bell));
+        assert_eq!(subscriber.backlog.len(), 3);
+        // only one event fits in the channel
+        subscriber.finish();
+        drop(subscriber);
+        assert!(matches!(
+            runtime.block_on(subscription.collect()),
+            Err(crate::error::Error::SubscriberLagged)
+        ));
+
+        let (mut subscriber, subscription) = Subscriber::new(
+            SubscribeOptions {
+                buffer_size: 4,
+                ..options(LagPolicy::Drop)
+            },
+            replay.clone(),
+        );
+        assert!(subscriber.send(&crate::process::Event::Bell));
+        subscriber.finish();
+        drop(subscriber);
+        let mut expected = replay;
+        expected.push(crate::process::Event::Bell);
+        assert_eq!(
+            runtime.block_on(subscription.collect()).unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_zero_buffer_size() {
+        let (mut subscriber, subscription) = Subscriber::new(
+            SubscribeOptions {
+                buffer_size: 0,
+                lag_policy: LagPolicy::Drop,
+                filter: None,
+            },
+            vec![],
+        );
+        assert!(subscriber.send(&crate::process::Event::Bell));
+        subscriber.finish();
+        drop(subscriber);
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        assert_eq!(
+            runtime.block_on(subscription.collect()).unwrap(),
+            vec![crate::process::Event::Bell]
+        );
+    }
+}
@@ -0,0 +1,345 @@
+use std::fmt::Write as _;
+
+/// Options controlling how `SvgRecorder` renders a recording.
+pub struct SvgOptions {
+    /// The colors to use for the default foreground and background colors
+    /// and the indexed color palette.
+    pub colors: crate::query::TerminalProfile,
+
+    /// The font size, in pixels. Each cell is 0.6 times this wide and 1.2
+    /// times this tall.
+    pub font_size: u16,
+
+    /// How long to keep showing the final frame before the animation loops.
+    pub final_delay: std::time::Duration,
+}
+
+impl Default for SvgOptions {
+    fn default() -> Self {
+        Self {
+            colors: crate::query::TerminalProfile::default(),
+            font_size: 14,
+            final_delay: std::time::Duration::from_secs(2),
+        }
+    }
+}
+
+struct Frame {
+    at: std::time::Duration,
+    size: (u16, u16),
+    contents: String,
+}
+
+/// Records a session from the events generated by a `TimedProcess`, and
+/// renders it as an animated SVG.
+///
+/// All output is applied to an in-memory terminal screen, and a frame is
+/// recorded each time the screen changes, so cursor movement, redraws, and
+/// full screen programs are shown as they would have appeared in a real
+/// terminal. The generated SVG contains the text of each frame (rather than
+/// rasterized images), and uses a CSS animation to show each frame at the
+/// time it was originally displayed.
+pub struct SvgRecorder {
+    parser: vt100::Parser,
+    options: SvgOptions,
+    start: Option<std::time::Instant>,
+    frames: Vec<Frame>,
+}
+
+impl SvgRecorder {
+    /// Creates a new recorder for a terminal of the given initial size.
+    /// `Event::Resize` events (as generated when the `TimedProcess` wraps a
+    /// `ResizingProcess`) will change the size of the terminal.
+    #[must_use]
+    pub fn new(rows: u16, cols: u16, options: SvgOptions) -> Self {
+        Self {
+            parser: vt100::Parser::new(rows, cols, 0),
+            options,
+            start: None,
+            frames: vec![],
+        }
+    }
+
+    /// Records an event. Events must be passed in the order they were
+    /// generated.
+    ///
+    /// Timing is relative to the first event recorded, which will usually
+    /// be `Event::CommandStart`.
+    pub fn record(&mut self, event: &crate::timed::TimedEvent) {
+        let start = *self.start.get_or_insert(event.at);
+        match &event.event {
+            crate::process::Event::Output { data } => {
+                self.parser.process(data);
+            }
+            crate::process::Event::Resize {
+                size: (rows, cols), ..
+            } => {
+                self.parser.screen_mut().set_size(*rows, *cols);
+            }
+            _ => {
+                if !self.frames.is_empty() {
+                    return;
+                }
+            }
+        }
+
+        let contents = self.render_frame();
+        if self.frames.last().is_some_and(|f| f.contents == contents) {
+            return;
+        }
+        self.frames.push(Frame {
+            at: event.at.saturating_duration_since(start),
+            size: self.parser.screen().size(),
+            contents,
+        });
+    }
+
+    /// Returns the number of distinct frames recorded so far.
+    #[must_use]
+    pub fn frames(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Renders the recording as an SVG document.
+    #[must_use]
+    pub fn to_svg(&self) -> String {
+        let (cell_width, cell_height) = self.cell_size();
+        let rows = self.frames.iter().map(|f| f.size.0).max().unwrap_or(0);
+        let cols = self.frames.iter().map(|f| f.size.1).max().unwrap_or(0);
+        let width = f64::from(cols) * cell_width;
+        let height = f64::from(rows) * cell_height;
+        let duration = self
+            .frames
+            .last()
+            .map_or(std::time::Duration::from_secs(0), |f| f.at)
+            + self.options.final_delay;
+        let duration = duration.as_secs_f64().max(0.001);
+
+        let mut svg = String::new();
+        let _ = writeln!(
+            svg,
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" \
+             height=\"{h}\" viewBox=\"0 0 {w} {h}\" \
+             font-family=\"monospace\" font-size=\"{}\">",
+            self.options.font_size,
+            w = num(width),
+            h = num(height),
+        );
+
+        // all frames are stacked vertically, and the animation moves each
+        // one into view in turn
+        svg.push_str("<style>\n");
+        let _ = writeln!(
+            svg,
+            ".screen {{ animation: play {duration:.3}s step-end infinite; }}"
+        );
+        svg.push_str("@keyframes play {\n");
+        let mut offset = 0.0;
+        for frame in &self.frames {
+            let _ = writeln!(
+                svg,
+                "  {:.3}% {{ transform: translateY({}px); }}",
+                frame.at.as_secs_f64() / duration * 100.0,
+                num(-offset),
+            );
+            offset += height;
+        }
+        if !self.frames.is_empty() {
+            let _ = writeln!(
+                svg,
+                "  100% {{ transform: translateY({}px); }}",
+                num(height - offset),
+            );
+        }
+        svg.push_str("}\ntext { white-space: pre; }\n</style>\n");
+
+        let _ = writeln!(
+            svg,
+            "<rect width=\"100%\" height=\"100%\" fill=\"{}\"/>",
+            crate::html::css_color(self.options.colors.background)
+        );
+        svg.push_str("<g class=\"screen\">\n");
+        let mut offset = 0.0;
+        for frame in &self.frames {
+            let _ = writeln!(
+                svg,
+                "<g transform=\"translate(0 {})\">",
+                num(offset)
+            );
+            offset += height;
+            svg.push_str(&frame.contents);
+            svg.push_str("</g>\n");
+        }
+        svg.push_str("</g>\n</svg>\n");
+        svg
+    }
+
+    fn cell_size(&self) -> (f64, f64) {
+        let font_size = f64::from(self.options.font_size);
+        (font_size * 0.6, font_size * 1.2)
+    }
+
+    fn render_frame(&self) -> String {
+        let (cell_width, cell_height) = self.cell_size();
+        let screen = self.parser.screen();
+        let colors = &self.options.colors;
+        let (rows, cols) = screen.size();
+
+        let mut backgrounds = String::new();
+        let mut text = String::new();
+        for row in 0..rows {
+            let y = f64::from(row) * cell_height;
+            let mut spans: Vec<(u16, crate::html::Style, String)> = vec![];
+            for col in 0..cols {
+                let Some(cell) = screen.cell(row, col) else {
+                    continue;
+                };
+                if cell.is_wide_continuation() {
+                    continue;
+                }
+                let style = crate::html::cell_style(cell, None, colors);
+                let width = if cell.is_wide() { 2.0 } else { 1.0 };
+                if let Some(bg) = style.bg {
+                    let _ = writeln!(
+                        backgrounds,
+                        "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" \
+                         fill=\"{}\"/>",
+                        num(f64::from(col) * cell_width),
+                        num(y),
+                        num(width * cell_width),
+                        num(cell_height),
+                        crate::html::css_color(bg)
+                    );
+                }
+                if !cell.has_contents() {
+                    continue;
+                }
+                match spans.last_mut() {
+                    // only extend runs of adjacent single width characters,
+                    // so that each run is positioned correctly regardless of
+                    // how the viewer's font renders wide characters
+                    Some((start, last, contents))
+                        if *last == style
+                            && !cell.is_wide()
+                            && usize::from(col - *start)
+                                == contents.chars().count() =>
+                    {
+                        contents.push_str(cell.contents());
+                    }
+                    _ => {
+                        spans.push((col, style, cell.contents().to_string()));
+                    }
+                }
+            }
+            if spans.is_empty() {
+                continue;
+            }
+
+            let _ = write!(
+                text,
+                "<text y=\"{}\" dominant-baseline=\"text-before-edge\">",
+                num(y)
+            );
+            for (col, style, contents) in spans {
+                let _ = write!(
+                    text,
+                    "<tspan x=\"{}\" fill=\"{}\"",
+                    num(f64::from(col) * cell_width),
+                    crate::html::css_color(style.fg)
+                );
+                if style.bold {
+                    text.push_str(" font-weight=\"bold\"");
+                }
+                if style.italic {
+                    text.push_str(" font-style=\"italic\"");
+                }
+                if style.underline {
+                    text.push_str(" text-decoration=\"underline\"");
+                }
+                if style.dim {
+                    text.push_str(" opacity=\"0.5\"");
+                }
+                let _ = write!(
+                    text,
+                    ">{}</tspan>",
+                    crate::html::escape(&contents)
+                );
+            }
+            text.push_str("</text>\n");
+        }
+
+        if !screen.hide_cursor() {
+            let (row, col) = screen.cursor_position();
+            let _ = writeln!(
+                backgrounds,
+                "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" \
+                 fill=\"{}\"/>",
+                num(f64::from(col) * cell_width),
+                num(f64::from(row) * cell_height),
+                num(cell_width),
+                num(cell_height),
+                crate::html::css_color(colors.cursor)
+            );
+        }
+
+        backgrounds + &text
+    }
+}
+
+// formats a coordinate without excessive precision (or a negative zero)
+fn num(n: f64) -> String {
+    let s = format!("{:.2}", n + 0.0);
+    s.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_record() {
+        let start = std::time::Instant::now();
+        let event = |secs, event| crate::timed::TimedEvent {
+            at: start + std::time::Duration::from_secs(secs),
+            event,
+        };
+
+        let mut recorder = SvgRecorder::new(2, 10, SvgOptions::default());
+        recorder.record(&event(
+            0,
+            crate::process::Event::CommandStart {
+                cmd: "sh".to_string(),
+                args: vec![],
+            },
+        ));
+        recorder.record(&event(
+            1,
+            crate::process::Event::Output {
+                data: b"hello".to_vec(),
+            },
+        ));
+        recorder.record(&event(
+            2,
+            crate::process::Event::Output {
+                data: b"\x1b[H\x1b[31mj".to_vec(),
+            },
+        ));
+        // no visible change
+        recorder.record(&event(
+            3,
+            crate::process::Event::Output {
+                data: b"\x1b[m".to_vec(),
+            },
+        ));
+        assert_eq!(recorder.frames(), 3);
+
+        let svg = recorder.to_svg();
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.contains("animation: play 4.000s"), "{}", svg);
+        assert!(svg.contains("25.000% { transform: translateY(-33.6px); }"));
+        assert!(svg.contains("50.000% { transform: translateY(-67.2px); }"));
+        assert!(svg.contains(">hello</tspan>"));
+        assert!(svg.contains("fill=\"#cd0000\">j</tspan>"));
+        assert!(svg.contains(">ello</tspan>"));
+    }
+}
@@ -0,0 +1,852 @@
+use std::os::unix::ffi::OsStringExt as _;
+
+const DEFAULT_ROWS: u16 = 24;
+const DEFAULT_COLS: u16 = 80;
+// matches xterm
+const TITLE_STACK_SIZE: usize = 10;
+// the selections which OSC 52 can name, as accepted by vt100
+const CLIPBOARD_SELECTIONS: &[u8] = b"cpqs01234567";
+
+// tracks the state of the terminal that the process is drawing to, so that
+// we can report on things like the screen contents and answer queries which
+// depend on the cursor position
+pub struct Terminal {
+    parser: vt100::Parser<State>,
+    scanner: Scanner,
+    hyperlinks: crate::hyperlink::HyperlinkExtractor,
+}
+
+// vt100 doesn't tell us where in the output each sequence it handles
+// occurs, so the output is also run through a separate parser which just
+// finds the ends of the sequences which can generate events. the output is
+// then fed to vt100 in pieces split at those points, so that events can be
+// reported at the correct offset within the output.
+#[derive(Default)]
+struct Scanner {
+    parser: vte::Parser,
+    boundary: Boundary,
+}
+
+#[derive(Default)]
+struct Boundary {
+    found: bool,
+    string_terminated: bool,
+    // whether the OSC which ended the segment was terminated by BEL rather
+    // than ST, so that replies to it can use the same terminator
+    bell_terminated: bool,
+    // the parameters of the DECSET or DECRST sequence which ended the
+    // segment, so that the mode changes can be reported in the same order
+    mode_params: Vec<u16>,
+}
+
+impl Scanner {
+    // returns the length of the prefix of data which ends at the next
+    // boundary (or all of data, if there isn't one)
+    fn next_boundary(&mut self, data: &[u8]) -> usize {
+        let mut len = 0;
+        // vte's fast path for plain text executes control characters
+        // without checking for termination, so split after each BEL to
+        // make sure we see them
+        for piece in data.split_inclusive(|&c| c == b'\x07') {
+            len += self
+                .parser
+                .advance_until_terminated(&mut self.boundary, piece);
+            if std::mem::take(&mut self.boundary.found) {
+                break;
+            }
+        }
+        // vte dispatches an OSC as soon as it sees the ESC at the start of
+        // ST, so include the rest of the terminator in this segment too
+        if std::mem::take(&mut self.boundary.string_terminated)
+            && data.get(len) == Some(&b'\\')
+        {
+            self.parser.advance(&mut self.boundary, &data[len..=len]);
+            len += 1;
+        }
+        len
+    }
+}
+
+impl vte::Perform for Boundary {
+    fn execute(&mut self, byte: u8) {
+        if byte == b'\x07' {
+            self.found = true;
+        }
+    }
+
+    fn osc_dispatch(&mut self, _params: &[&[u8]], bell_terminated: bool) {
+        self.found = true;
+        self.string_terminated = !bell_terminated;
+        self.bell_terminated = bell_terminated;
+    }
+
+    fn csi_dispatch(
+        &mut self,
+        params: &vte::Params,
+        intermediates: &[u8],
+        _ignore: bool,
+        c: char,
+    ) {
+        match (intermediates, c) {
+            // XTWINOPS, for the title stack
+            ([], 't') => self.found = true,
+            // DECSET and DECRST
+            ([b'?'], 'h' | 'l') => {
+                self.found = true;
+                self.mode_params = params
+                    .iter()
+                    .filter_map(|p| p.first().copied())
+                    .collect();
+            }
+            _ => {}
+        }
+    }
+
+    fn terminated(&self) -> bool {
+        self.found
+    }
+}
+
+#[derive(Default)]
+struct State {
+    profile: Option<crate::query::TerminalProfile>,
+    replies: Vec<u8>,
+    events: Vec<crate::process::Event>,
+    title: String,
+    icon_name: String,
+    title_stack: Vec<String>,
+    icon_name_stack: Vec<String>,
+    cwd: Option<std::path::PathBuf>,
+    cwd_host: String,
+    // set whenever the process reports its working directory, even if it
+    // didn't change
+    cwd_reported: bool,
+    // vt100 doesn't track these itself
+    focus_reporting: bool,
+    urxvt_mouse: bool,
+    clipboard: crate::clipboard::Clipboard,
+    // the terminator of the OSC sequence currently being handled
+    osc_terminator: &'static str,
+}
+
+impl State {
+    fn set_title(&mut self, title: String) {
+        if title != self.title {
+            self.title.clone_from(&title);
+            self.events
+                .push(crate::process::Event::TitleChanged { title });
+        }
+    }
+
+    fn set_icon_name(&mut self, icon_name: String) {
+        if icon_name != self.icon_name {
+            self.icon_name.clone_from(&icon_name);
+            self.events
+                .push(crate::process::Event::IconNameChanged { icon_name });
+        }
+    }
+
+    // XTWINOPS 22 and 23 - the second parameter selects whether to operate
+    // on both the icon name and window title (0), just the icon name (1),
+    // or just the window title (2)
+    fn push_title(&mut self, which: u16) {
+        if which == 0 || which == 1 {
+            if self.icon_name_stack.len() >= TITLE_STACK_SIZE {
+                self.icon_name_stack.remove(0);
+            }
+            self.icon_name_stack.push(self.icon_name.clone());
+        }
+        if which == 0 || which == 2 {
+            if self.title_stack.len() >= TITLE_STACK_SIZE {
+                self.title_stack.remove(0);
+            }
+            self.title_stack.push(self.title.clone());
+        }
+    }
+
+    // OSC 52 ; <selections> ; <base64 data>
+    fn copy(&mut self, ty: &[u8], data: &[u8]) {
+        let param = String::from_utf8_lossy(ty);
+        for (selection, data) in self.clipboard.copy(&param, data) {
+            self.events.push(crate::process::Event::ClipboardSet {
+                selection,
+                data,
+            });
+        }
+    }
+
+    // OSC 7 ; file://<host>/<path>
+    fn set_cwd(&mut self, url: &[u8]) {
+        let Some(rest) = url.strip_prefix(b"file://") else {
+            return;
+        };
+        let Some(idx) = rest.iter().position(|&c| c == b'/') else {
+            return;
+        };
+        let host = String::from_utf8_lossy(&rest[..idx]).into_owned();
+        let cwd = std::path::PathBuf::from(std::ffi::OsString::from_vec(
+            percent_decode(&rest[idx..]),
+        ));
+        self.cwd_reported = true;
+        if self.cwd.as_ref() != Some(&cwd) || self.cwd_host != host {
+            self.cwd = Some(cwd.clone());
+            self.cwd_host.clone_from(&host);
+            self.events
+                .push(crate::process::Event::CwdChanged { host, cwd });
+        }
+    }
+
+    // OSC 133 ; <mark> [; <args>...] - semantic prompt markers, as used by
+    // FinalTerm and most modern terminals. any additional key=value options
+    // are ignored.
+    fn mark(&mut self, mark: &[u8], args: &[&[u8]]) {
+        let event = match mark {
+            b"A" => crate::process::Event::PromptStart,
+            b"B" => crate::process::Event::CommandInput,
+            b"C" => crate::process::Event::CommandOutputStart,
+            b"D" => crate::process::Event::CommandFinished {
+                exit_code: args.first().and_then(|code| {
+                    std::str::from_utf8(code).ok()?.parse().ok()
+                }),
+            },
+            _ => return,
+        };
+        self.events.push(event);
+    }
+
+    fn pop_title(&mut self, which: u16) {
+        if which == 0 || which == 1 {
+            if let Some(icon_name) = self.icon_name_stack.pop() {
+                self.set_icon_name(icon_name);
+            }
+        }
+        if which == 0 || which == 2 {
+            if let Some(title) = self.title_stack.pop() {
+                self.set_title(title);
+            }
+        }
+    }
+}
+
+impl Terminal {
+    pub fn new() -> Self {
+        Self::with_scrollback(0)
+    }
+
+    pub fn with_scrollback(scrollback_len: usize) -> Self {
+        Self {
+            parser: vt100::Parser::new_with_callbacks(
+                DEFAULT_ROWS,
+                DEFAULT_COLS,
+                scrollback_len,
+                State::default(),
+            ),
+            scanner: Scanner::default(),
+            hyperlinks: crate::hyperlink::HyperlinkExtractor::new(),
+        }
+    }
+
+    // returns the events generated by the output, along with the offset in
+    // data at which each one occurred (the end of the sequence which
+    // generated it)
+    pub fn process(
+        &mut self,
+        data: &[u8],
+    ) -> Vec<(usize, crate::process::Event)> {
+        let mut events = vec![];
+        let mut start = 0;
+        while start < data.len() {
+            let end = start + self.scanner.next_boundary(&data[start..]);
+            let mode_params =
+                std::mem::take(&mut self.scanner.boundary.mode_params);
+            self.parser.callbacks_mut().osc_terminator =
+                if std::mem::take(&mut self.scanner.boundary.bell_terminated)
+                {
+                    "\x07"
+                } else {
+                    "\x1b\\"
+                };
+            self.process_segment(&data[start..end], &mode_params);
+            events.extend(
+                std::mem::take(&mut self.parser.callbacks_mut().events)
+                    .into_iter()
+                    .map(|event| (end, event)),
+            );
+            // every OSC ends a segment, so links are reported just after
+            // the sequence which closed them
+            events.extend(
+                self.hyperlinks.process(&data[start..end]).into_iter().map(
+                    |link| (end, crate::process::Event::Hyperlink { link }),
+                ),
+            );
+            start = end;
+        }
+        events
+    }
+
+    fn process_segment(&mut self, data: &[u8], mode_params: &[u16]) {
+        // each DECSET or DECRST ends a segment, so comparing the modes
+        // before and after the segment catches every change made by it.
+        // changes are reported in the order of the parameters which made
+        // them (anything else which changes modes, like a terminal reset,
+        // is reported in the order of Mode::ALL).
+        let prev = crate::mode::Mode::ALL.map(|mode| self.mode(mode));
+        self.parser.process(data);
+        let mut changed: Vec<_> = crate::mode::Mode::ALL
+            .iter()
+            .copied()
+            .zip(prev)
+            .filter(|&(mode, prev)| self.mode(mode) != prev)
+            .map(|(mode, _)| mode)
+            .collect();
+        changed.sort_by_key(|&mode| {
+            mode_params
+                .iter()
+                .position(|&param| {
+                    crate::mode::Mode::from_decset(param) == Some(mode)
+                })
+                .unwrap_or(usize::MAX)
+        });
+        for mode in changed {
+            let enabled = self.mode(mode);
+            self.parser
+                .callbacks_mut()
+                .events
+                .push(crate::process::Event::ModeChanged { mode, enabled });
+        }
+    }
+
+    pub fn mode(&self, mode: crate::mode::Mode) -> bool {
+        let screen = self.screen();
+        match mode {
+            crate::mode::Mode::AlternateScreen => screen.alternate_screen(),
+            crate::mode::Mode::MouseReporting => {
+                screen.mouse_protocol_mode() != vt100::MouseProtocolMode::None
+            }
+            crate::mode::Mode::BracketedPaste => screen.bracketed_paste(),
+            crate::mode::Mode::FocusReporting => {
+                self.parser.callbacks().focus_reporting
+            }
+            crate::mode::Mode::ApplicationCursor => {
+                screen.application_cursor()
+            }
+        }
+    }
+
+    pub fn screen(&self) -> &vt100::Screen {
+        self.parser.screen()
+    }
+
+    pub fn screen_mut(&mut self) -> &mut vt100::Screen {
+        self.parser.screen_mut()
+    }
+
+    pub fn set_size(&mut self, rows: u16, cols: u16) {
+        self.parser.screen_mut().set_size(rows, cols);
+    }
+
+    pub fn set_profile(
+        &mut self,
+        profile: Option<crate::query::TerminalProfile>,
+    ) {
+        self.parser.callbacks_mut().profile = profile;
+    }
+
+    pub fn set_clipboard_read_policy(
+        &mut self,
+        policy: crate::clipboard::ClipboardReadPolicy,
+    ) {
+        self.parser.callbacks_mut().clipboard.set_policy(policy);
+    }
+
+    pub fn take_replies(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.parser.callbacks_mut().replies)
+    }
+
+    // xterm only allows one of the extended encodings to be active at a
+    // time, but since vt100 tracks 1005 and 1006 separately from 1015, we
+    // just prefer the more capable encodings if multiple are enabled
+    pub fn mouse_encoding(&self) -> crate::mouse::MouseEncoding {
+        match self.screen().mouse_protocol_encoding() {
+            vt100::MouseProtocolEncoding::Sgr => {
+                crate::mouse::MouseEncoding::Sgr
+            }
+            _ if self.parser.callbacks().urxvt_mouse => {
+                crate::mouse::MouseEncoding::Urxvt
+            }
+            vt100::MouseProtocolEncoding::Utf8 => {
+                crate::mouse::MouseEncoding::Utf8
+            }
+            vt100::MouseProtocolEncoding::Default => {
+                crate::mouse::MouseEncoding::Default
+            }
+        }
+    }
+
+    pub fn title(&self) -> &str {
+        &self.parser.callbacks().title
+    }
+
+    pub fn icon_name(&self) -> &str {
+        &self.parser.callbacks().icon_name
+    }
+
+    pub fn cwd(&self) -> Option<&std::path::Path> {
+        self.parser.callbacks().cwd.as_deref()
+    }
+
+    pub fn cwd_host(&self) -> &str {
+        &self.parser.callbacks().cwd_host
+    }
+
+    pub fn take_cwd_reported(&mut self) -> bool {
+        std::mem::take(&mut self.parser.callbacks_mut().cwd_reported)
+    }
+
+    // recreating the parser loses the existing state, so this should only
+    // be called before any output has been processed
+    pub fn set_scrollback(&mut self, scrollback_len: usize) {
+        let (rows, cols) = self.screen().size();
+        let state = std::mem::take(self.parser.callbacks_mut());
+        self.parser = vt100::Parser::new_with_callbacks(
+            rows,
+            cols,
+            scrollback_len,
+            state,
+        );
+    }
+}
+
+impl vt100::Callbacks for State {
+    fn audible_bell(&mut self, _: &mut vt100::Screen) {
+        self.events.push(crate::process::Event::Bell);
+    }
+
+    fn set_window_title(&mut self, _: &mut vt100::Screen, title: &[u8]) {
+        self.set_title(String::from_utf8_lossy(title).into_owned());
+    }
+
+    fn set_window_icon_name(
+        &mut self,
+        _: &mut vt100::Screen,
+        icon_name: &[u8],
+    ) {
+        self.set_icon_name(String::from_utf8_lossy(icon_name).into_owned());
+    }
+
+    fn copy_to_clipboard(
+        &mut self,
+        _: &mut vt100::Screen,
+        ty: &[u8],
+        data: &[u8],
+    ) {
+        self.copy(ty, data);
+    }
+
+    fn paste_from_clipboard(&mut self, _: &mut vt100::Screen, ty: &[u8]) {
+        let selection = String::from_utf8_lossy(ty);
+        if let Some(reply) =
+            self.clipboard.reply_paste(&selection, self.osc_terminator)
+        {
+            log::debug!("reply({reply:?})");
+            self.replies.extend(reply);
+        }
+    }
+
+    fn unhandled_csi(
+        &mut self,
+        screen: &mut vt100::Screen,
+        i1: Option<u8>,
+        _i2: Option<u8>,
+        params: &[&[u16]],
+        c: char,
+    ) {
+        // vt100 passes the full parameter list for each parameter it doesn't
+        // recognize, so this may be called multiple times for the same
+        // sequence
+        if i1 == Some(b'?') && (c == 'h' || c == 'l') {
+            for param in params {
+                match param {
+                    [1004] => self.focus_reporting = c == 'h',
+                    [1015] => self.urxvt_mouse = c == 'h',
+                    _ => {}
+                }
+            }
+        }
+
+        if i1.is_none() && c == 't' {
+            let op = params.first().and_then(|p| p.first()).copied();
+            let which =
+                params.get(1).and_then(|p| p.first()).copied().unwrap_or(0);
+            match op {
+                Some(22) => self.push_title(which),
+                Some(23) => self.pop_title(which),
+                _ => {}
+            }
+        }
+
+        if let Some(profile) = &self.profile {
+            if let Some(reply) = profile.reply_csi(screen, i1, params, c) {
+                log::debug!("reply({reply:?})");
+                self.replies.extend(reply);
+            }
+        }
+    }
+
+    fn unhandled_osc(&mut self, _: &mut vt100::Screen, params: &[&[u8]]) {
+        match params {
+            [b"7", url] => self.set_cwd(url),
+            [b"133", mark, args @ ..] => self.mark(mark, args),
+            // vt100 only handles data which looks like base64, but invalid
+            // data still clears the selection
+            [b"52", ty, data]
+                if ty.iter().all(|c| CLIPBOARD_SELECTIONS.contains(c)) =>
+            {
+                self.copy(ty, data);
+            }
+            _ => {}
+        }
+
+        if let Some(profile) = &self.profile {
+            if let Some(reply) =
+                profile.reply_osc(params, self.osc_terminator)
+            {
+                log::debug!("reply({reply:?})");
+                self.replies.extend(reply);
+            }
+        }
+    }
+}
+
+fn percent_decode(s: &[u8]) -> Vec<u8> {
+    let mut decoded = vec![];
+    let mut i = 0;
+    while i < s.len() {
+        if s[i] == b'%' {
+            let hex = s.get(i + 1..i + 3).and_then(|hex| {
+                std::str::from_utf8(hex)
+                    .ok()
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            });
+            if let Some(c) = hex {
+                decoded.push(c);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(s[i]);
+        i += 1;
+    }
+    decoded
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::process::Event;
+
+    fn process_events(term: &mut Terminal, data: &[u8]) -> Vec<Event> {
+        term.process(data)
+            .into_iter()
+            .map(|(_, event)| event)
+            .collect()
+    }
+
+    #[test]
+    fn test_title() {
+        let mut term = Terminal::new();
+        let events = process_events(&mut term, b"\x1b]0;both\x07");
+        assert_eq!(
+            events,
+            vec![
+                Event::IconNameChanged {
+                    icon_name: "both".to_string()
+                },
+                Event::TitleChanged {
+                    title: "both".to_string()
+                },
+            ]
+        );
+        let events = process_events(&mut term, b"\x1b]2;both\x07");
+        assert_eq!(events, vec![]);
+
+        let events = process_events(
+            &mut term,
+            b"\x1b[22;2t\x1b]2;vim\x1b\\\x1b]1;icon\x07",
+        );
+        assert_eq!(term.title(), "vim");
+        assert_eq!(term.icon_name(), "icon");
+        assert_eq!(
+            events,
+            vec![
+                Event::TitleChanged {
+                    title: "vim".to_string()
+                },
+                Event::IconNameChanged {
+                    icon_name: "icon".to_string()
+                },
+            ]
+        );
+
+        let events = process_events(&mut term, b"\x1b[23;0t");
+        assert_eq!(term.title(), "both");
+        assert_eq!(term.icon_name(), "icon");
+        assert_eq!(
+            events,
+            vec![Event::TitleChanged {
+                title: "both".to_string()
+            }]
+        );
+    }
+
+    #[test]
+    fn test_cwd() {
+        let mut term = Terminal::new();
+        assert_eq!(term.cwd(), None);
+        let events = process_events(
+            &mut term,
+            b"\x1b]7;file://myhost/home/doy/a%20b%2\x07",
+        );
+        assert_eq!(term.cwd(), Some(std::path::Path::new("/home/doy/a b%2")));
+        assert_eq!(
+            events,
+            vec![Event::CwdChanged {
+                host: "myhost".to_string(),
+                cwd: std::path::PathBuf::from("/home/doy/a b%2"),
+            }]
+        );
+        let events = process_events(
+            &mut term,
+            b"\x1b]7;file:///tmp\x1b\\\x1b]7;not a url\x07",
+        );
+        assert_eq!(term.cwd(), Some(std::path::Path::new("/tmp")));
+        assert_eq!(events.len(), 1);
+    }
+
+    #[test]
+    fn test_marks() {
+        let mut term = Terminal::new();
+        let mut events = process_events(
+            &mut term,
+            b"\x1b]133;A\x07$ \x1b]133;B\x07ls\r\n",
+        );
+        events.extend(process_events(
+            &mut term,
+            b"\x1b]133;C;cmdline=ls\x07foo\r\n\x1b]133;D;1\x07",
+        ));
+        events.extend(process_events(
+            &mut term,
+            b"\x1b]133;D\x1b\\\x1b]133;Z\x07",
+        ));
+        assert_eq!(
+            events,
+            vec![
+                Event::PromptStart,
+                Event::CommandInput,
+                Event::CommandOutputStart,
+                Event::CommandFinished { exit_code: Some(1) },
+                Event::CommandFinished { exit_code: None },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_offsets() {
+        let mut term = Terminal::new();
+        let offsets = |events: Vec<(usize, Event)>| {
+            events
+                .into_iter()
+                .map(|(offset, _)| offset)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            offsets(term.process(b"\x1b]133;C\x07foo\x1b]133;D;1\x07bar")),
+            vec![8, 21]
+        );
+        assert_eq!(offsets(term.process(b"a\x07b\x07\x07")), vec![2, 4, 5]);
+        assert_eq!(
+            offsets(term.process(b"\x1b[22t\x1b]2;x\x1b\\y\x1b[23t")),
+            vec![12, 18]
+        );
+    }
+
+    #[test]
+    fn test_hyperlinks() {
+        let mut term = Terminal::new();
+        assert_eq!(
+            term.process(b"see \x1b]8;;https://a\x07x\x1b]8;id=1;https://b"),
+            vec![]
+        );
+        let link = |uri: &str, id: Option<&str>, text: &str, offset| {
+            Event::Hyperlink {
+                link: crate::hyperlink::Hyperlink {
+                    uri: uri.to_string(),
+                    id: id.map(str::to_string),
+                    text: text.to_string(),
+                    offset,
+                },
+            }
+        };
+        // each link is reported just after the sequence which closed it,
+        // even if that was split across chunks
+        assert_eq!(
+            term.process(b"\x1b\\y\x1b]8;;\x1b"),
+            vec![
+                (2, link("https://a", None, "x", 19)),
+                (9, link("https://b", Some("1"), "y", 40)),
+            ]
+        );
+        assert_eq!(term.process(b"\\z"), vec![]);
+        assert_eq!(term.screen().contents(), "see xyz");
+    }
+
+    #[test]
+    fn test_modes() {
+        use crate::mode::Mode;
+
+        let mut term = Terminal::new();
+        let events =
+            process_events(&mut term, b"\x07\x1b[?1049h\x1b[?1;1004;1000h");
+        assert!(term.mode(Mode::AlternateScreen));
+        assert!(term.mode(Mode::MouseReporting));
+        assert!(term.mode(Mode::FocusReporting));
+        assert!(!term.mode(Mode::BracketedPaste));
+        assert_eq!(
+            events,
+            vec![
+                Event::Bell,
+                Event::ModeChanged {
+                    mode: Mode::AlternateScreen,
+                    enabled: true
+                },
+                Event::ModeChanged {
+                    mode: Mode::ApplicationCursor,
+                    enabled: true
+                },
+                Event::ModeChanged {
+                    mode: Mode::FocusReporting,
+                    enabled: true
+                },
+                Event::ModeChanged {
+                    mode: Mode::MouseReporting,
+                    enabled: true
+                },
+            ]
+        );
+
+        // a mode which is toggled within a single chunk is still reported
+        let events = process_events(
+            &mut term,
+            b"\x1b[?1004l\x1b[?2004h\x1b[?1049l\x1b[?1049h\x1b[?1000h",
+        );
+        assert_eq!(
+            events,
+            vec![
+                Event::ModeChanged {
+                    mode: Mode::FocusReporting,
+                    enabled: false
+                },
+                Event::ModeChanged {
+                    mode: Mode::BracketedPaste,
+                    enabled: true
+                },
+                Event::ModeChanged {
+                    mode: Mode::AlternateScreen,
+                    enabled: false
+                },
+                Event::ModeChanged {
+                    mode: Mode::AlternateScreen,
+                    enabled: true
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_mouse_encoding() {
+        use crate::mouse::MouseEncoding;
+
+        let mut term = Terminal::new();
+        assert_eq!(term.mouse_encoding(), MouseEncoding::Default);
+        term.process(b"\x1b[?1000;1015h");
+        assert_eq!(term.mouse_encoding(), MouseEncoding::Urxvt);
+        term.process(b"\x1b[?1006h");
+        assert_eq!(term.mouse_encoding(), MouseEncoding::Sgr);
+        term.process(b"\x1b[?1006;1015l\x1b[?1005h");
+        assert_eq!(term.mouse_encoding(), MouseEncoding::Utf8);
+    }
+
+    #[test]
+    fn test_clipboard() {
+        use crate::clipboard::ClipboardReadPolicy;
+
+        let mut term = Terminal::new();
+        let events = process_events(
+            &mut term,
+            b"\x1b]52;c;aGVsbG8=\x07\x1b]52;c;?\x07",
+        );
+        assert_eq!(
+            events,
+            vec![Event::ClipboardSet {
+                selection: "c".to_string(),
+                data: b"hello".to_vec(),
+            }]
+        );
+        assert_eq!(term.take_replies(), b"");
+
+        // nothing is kept while reads are denied
+        term.set_clipboard_read_policy(ClipboardReadPolicy::Internal);
+        term.process(b"\x1b]52;c;?\x07");
+        assert_eq!(term.take_replies(), &b"\x1b]52;c;\x07"[..]);
+
+        let events = process_events(&mut term, b"\x1b]52;pc;aGVsbG8=\x07");
+        assert_eq!(
+            events,
+            vec![
+                Event::ClipboardSet {
+                    selection: "p".to_string(),
+                    data: b"hello".to_vec(),
+                },
+                Event::ClipboardSet {
+                    selection: "c".to_string(),
+                    data: b"hello".to_vec(),
+                },
+            ]
+        );
+        term.process(b"\x1b]52;c;?\x07\x1b]52;s;?\x1b\\");
+        assert_eq!(
+            term.take_replies(),
+            &b"\x1b]52;c;aGVsbG8=\x07\x1b]52;s;\x1b\\"[..]
+        );
+
+        // invalid data clears the selection
+        let events = process_events(&mut term, b"\x1b]52;p;!!!\x07");
+        assert_eq!(
+            events,
+            vec![Event::ClipboardSet {
+                selection: "p".to_string(),
+                data: vec![],
+            }]
+        );
+        term.process(b"\x1b]52;p;?\x07\x1b]52;pc;?\x07");
+        assert_eq!(
+            term.take_replies(),
+            &b"\x1b]52;p;\x07\x1b]52;pc;aGVsbG8=\x07"[..]
+        );
+
+        term.set_clipboard_read_policy(ClipboardReadPolicy::Custom(
+            Box::new(|selection| {
+                if selection == "p" {
+                    Some(b"hi".to_vec())
+                } else {
+                    None
+                }
+            }),
+        ));
+        term.process(b"\x1b]52;c;?\x07\x1b]52;p;?\x07\x1b]52;cp;?\x07");
+        assert_eq!(
+            term.take_replies(),
+            &b"\x1b]52;p;aGk=\x07\x1b]52;cp;aGk=\x07"[..]
+        );
+    }
+}
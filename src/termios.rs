@@ -0,0 +1,274 @@
+/// The terminal attributes of a pty, as returned by `Process::termios`.
+///
+/// This is a simplified view of the underlying `struct termios`, covering
+/// the settings which are most commonly changed by programs running in a
+/// terminal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+// these mirror independent flags of the underlying struct termios
+#[allow(clippy::struct_excessive_bools)]
+pub struct Termios {
+    /// Whether input characters are echoed back (`ECHO`). Programs
+    /// typically disable this when prompting for a password.
+    pub echo: bool,
+
+    /// Whether input is processed a line at a time, with line editing
+    /// (`ICANON`). When this is disabled, input is available to the program
+    /// immediately, as when it is in raw or cbreak mode.
+    pub canonical: bool,
+
+    /// Whether the interrupt, quit, and suspend characters generate signals
+    /// (`ISIG`).
+    pub signals: bool,
+
+    /// Whether output is post-processed (`OPOST`), for instance to translate
+    /// `\n` into `\r\n`.
+    pub output_processing: bool,
+
+    /// The character which sends `SIGINT` (`VINTR`), or `None` if disabled.
+    pub intr: Option<u8>,
+
+    /// The character which sends `SIGQUIT` (`VQUIT`), or `None` if
+    /// disabled.
+    pub quit: Option<u8>,
+
+    /// The character which erases the previous character (`VERASE`), or
+    /// `None` if disabled.
+    pub erase: Option<u8>,
+
+    /// The character which erases the current line (`VKILL`), or `None` if
+    /// disabled.
+    pub kill: Option<u8>,
+
+    /// The character which signals end of file (`VEOF`), or `None` if
+    /// disabled.
+    pub eof: Option<u8>,
+
+    /// The character which sends `SIGTSTP` (`VSUSP`), or `None` if
+    /// disabled.
+    pub susp: Option<u8>,
+
+    /// The minimum number of bytes for a read in non-canonical mode
+    /// (`VMIN`).
+    pub min: u8,
+
+    /// The timeout for a read in non-canonical mode, in tenths of a second
+    /// (`VTIME`).
+    pub time: u8,
+}
+
+/// A special character which can be set with `TermiosConfig`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpecialChar {
+    /// `VINTR`, which sends `SIGINT` (usually `^C`).
+    Intr,
+
+    /// `VQUIT`, which sends `SIGQUIT` (usually `^\`).
+    Quit,
+
+    /// `VERASE`, which erases the previous character (usually `^?`).
+    Erase,
+
+    /// `VKILL`, which erases the current line (usually `^U`).
+    Kill,
+
+    /// `VEOF`, which signals end of file (usually `^D`).
+    Eof,
+
+    /// `VSUSP`, which sends `SIGTSTP` (usually `^Z`).
+    Susp,
+}
+
+impl SpecialChar {
+    fn index(self) -> usize {
+        match self {
+            Self::Intr => libc::VINTR,
+            Self::Quit => libc::VQUIT,
+            Self::Erase => libc::VERASE,
+            Self::Kill => libc::VKILL,
+            Self::Eof => libc::VEOF,
+            Self::Susp => libc::VSUSP,
+        }
+    }
+}
+
+/// Terminal attributes to apply to the pty before the command is spawned,
+/// via `Process::configure_termios`.
+///
+/// Settings which are `None` are left at their defaults. If `raw` is set,
+/// the pty is put into raw mode first (as with `cfmakeraw`), and then any
+/// other settings are applied on top of that.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TermiosConfig {
+    /// Whether to put the pty into raw mode, disabling echo, line
+    /// buffering, signal generation, and input and output processing.
+    pub raw: bool,
+
+    /// Whether input characters should be echoed back (`ECHO`).
+    pub echo: Option<bool>,
+
+    /// Whether input should be processed a line at a time (`ICANON`).
+    pub canonical: Option<bool>,
+
+    /// Whether the interrupt, quit, and suspend characters should generate
+    /// signals (`ISIG`).
+    pub signals: Option<bool>,
+
+    /// Whether output should be post-processed (`OPOST`). Disabling this
+    /// prevents `\n` from being translated into `\r\n`.
+    pub output_processing: Option<bool>,
+
+    /// Special characters to set. A value of `None` disables the character.
+    pub special_chars: Vec<(SpecialChar, Option<u8>)>,
+
+    /// The minimum number of bytes for a read in non-canonical mode
+    /// (`VMIN`).
+    pub min: Option<u8>,
+
+    /// The timeout for a read in non-canonical mode, in tenths of a second
+    /// (`VTIME`).
+    pub time: Option<u8>,
+}
+
+impl TermiosConfig {
+    pub(crate) fn apply_to_fd(
+        &self,
+        fd: std::os::unix::io::RawFd,
+    ) -> std::io::Result<()> {
+        let mut termios = get_raw(fd)?;
+        self.apply(&mut termios);
+        // safe because termios is a valid, initialized termios struct
+        if unsafe {
+            libc::tcsetattr(fd, libc::TCSANOW, std::ptr::addr_of!(termios))
+        } != 0
+        {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    fn apply(&self, termios: &mut libc::termios) {
+        fn set_flag(
+            flags: &mut libc::tcflag_t,
+            flag: libc::tcflag_t,
+            on: Option<bool>,
+        ) {
+            match on {
+                Some(true) => *flags |= flag,
+                Some(false) => *flags &= !flag,
+                None => {}
+            }
+        }
+
+        if self.raw {
+            // safe because termios is a valid, initialized termios struct
+            unsafe { libc::cfmakeraw(termios) };
+        }
+        set_flag(&mut termios.c_lflag, libc::ECHO, self.echo);
+        set_flag(&mut termios.c_lflag, libc::ICANON, self.canonical);
+        set_flag(&mut termios.c_lflag, libc::ISIG, self.signals);
+        set_flag(&mut termios.c_oflag, libc::OPOST, self.output_processing);
+        for (c, value) in &self.special_chars {
+            termios.c_cc[c.index()] = value.unwrap_or(libc::_POSIX_VDISABLE);
+        }
+        if let Some(min) = self.min {
+            termios.c_cc[libc::VMIN] = min;
+        }
+        if let Some(time) = self.time {
+            termios.c_cc[libc::VTIME] = time;
+        }
+    }
+}
+
+impl Termios {
+    /// Returns true if the terminal is in raw mode (input is not line
+    /// buffered or echoed, and control characters don't generate signals).
+    #[must_use]
+    pub fn is_raw(&self) -> bool {
+        !self.echo && !self.canonical && !self.signals
+    }
+
+    pub(crate) fn from_fd(
+        fd: std::os::unix::io::RawFd,
+    ) -> std::io::Result<Self> {
+        Ok(Self::from_raw(&get_raw(fd)?))
+    }
+
+    fn from_raw(termios: &libc::termios) -> Self {
+        let cc = |idx: usize| {
+            let c = termios.c_cc[idx];
+            if c == libc::_POSIX_VDISABLE {
+                None
+            } else {
+                Some(c)
+            }
+        };
+        Self {
+            echo: termios.c_lflag & libc::ECHO != 0,
+            canonical: termios.c_lflag & libc::ICANON != 0,
+            signals: termios.c_lflag & libc::ISIG != 0,
+            output_processing: termios.c_oflag & libc::OPOST != 0,
+            intr: cc(libc::VINTR),
+            quit: cc(libc::VQUIT),
+            erase: cc(libc::VERASE),
+            kill: cc(libc::VKILL),
+            eof: cc(libc::VEOF),
+            susp: cc(libc::VSUSP),
+            min: termios.c_cc[libc::VMIN],
+            time: termios.c_cc[libc::VTIME],
+        }
+    }
+}
+
+fn get_raw(fd: std::os::unix::io::RawFd) -> std::io::Result<libc::termios> {
+    let mut termios = std::mem::MaybeUninit::<libc::termios>::uninit();
+    // safe because tcgetattr fully initializes termios when it succeeds
+    unsafe {
+        if libc::tcgetattr(fd, termios.as_mut_ptr()) != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(termios.assume_init())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_apply() {
+        // safe because termios is a plain c struct
+        let mut termios: libc::termios = unsafe { std::mem::zeroed() };
+        termios.c_lflag = libc::ECHO | libc::ICANON | libc::ISIG;
+        termios.c_oflag = libc::OPOST;
+        termios.c_cc[libc::VINTR] = 3;
+
+        let mut raw = termios;
+        TermiosConfig {
+            raw: true,
+            special_chars: vec![
+                (SpecialChar::Intr, None),
+                (SpecialChar::Eof, Some(b'q')),
+            ],
+            ..TermiosConfig::default()
+        }
+        .apply(&mut raw);
+        let raw = Termios::from_raw(&raw);
+        assert!(raw.is_raw());
+        assert!(!raw.output_processing);
+        assert_eq!(raw.intr, None);
+        assert_eq!(raw.eof, Some(b'q'));
+        assert_eq!(raw.min, 1);
+
+        TermiosConfig {
+            echo: Some(false),
+            output_processing: Some(true),
+            ..TermiosConfig::default()
+        }
+        .apply(&mut termios);
+        let termios = Termios::from_raw(&termios);
+        assert!(!termios.echo);
+        assert!(termios.canonical);
+        assert!(termios.output_processing);
+        assert_eq!(termios.intr, Some(3));
+    }
+}
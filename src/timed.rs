@@ -0,0 +1,202 @@
+/// An event along with the time at which it was observed.
+#[derive(Debug, PartialEq, Eq)]
+pub struct TimedEvent {
+    /// The time at which the event was observed by the process stream.
+    pub at: std::time::Instant,
+
+    /// The event itself.
+    pub event: crate::process::Event,
+}
+
+/// A stream of process events which knows when each of its events was
+/// observed, so that it can be wrapped in a `TimedProcess`.
+///
+/// This is implemented by `Process`, `ResizingProcess`, and `ScreenProcess`.
+pub trait TimedStream:
+    futures::stream::Stream<
+    Item = crate::process::Event,
+    Error = crate::error::Error,
+>
+{
+    /// Returns the time at which the event most recently returned by the
+    /// stream was observed.
+    fn last_event_time(&self) -> Option<std::time::Instant>;
+}
+
+impl<R: tokio::io::AsyncRead + 'static> TimedStream
+    for crate::process::Process<R>
+{
+    fn last_event_time(&self) -> Option<std::time::Instant> {
+        self.last_event_time()
+    }
+}
+
+impl<R: tokio::io::AsyncRead + 'static> TimedStream
+    for crate::resize::ResizingProcess<R>
+{
+    fn last_event_time(&self) -> Option<std::time::Instant> {
+        self.last_event_time()
+    }
+}
+
+impl<R: tokio::io::AsyncRead + 'static> TimedStream
+    for crate::screen::ScreenProcess<R>
+{
+    // screen updates are generated as soon as the output is read
+    fn last_event_time(&self) -> Option<std::time::Instant> {
+        self.process().last_event_time()
+    }
+}
+
+/// A wrapper around `Process` (or another stream of process events, such as
+/// `ResizingProcess`) which annotates each event with the time at which it
+/// happened.
+///
+/// The timestamps are taken when the underlying data is read from the pty or
+/// when the process exit is reaped, rather than when the event is returned
+/// from the stream, so they are suitable for recording sessions or measuring
+/// latency without including scheduler delay.
+#[must_use = "streams do nothing unless polled"]
+pub struct TimedProcess<S: TimedStream> {
+    stream: S,
+}
+
+impl<S: TimedStream> TimedProcess<S> {
+    /// Creates a new `TimedProcess` as a wrapper around the given stream.
+    pub fn new(stream: S) -> Self {
+        Self { stream }
+    }
+
+    /// Returns a reference to the wrapped stream.
+    pub fn get_ref(&self) -> &S {
+        &self.stream
+    }
+
+    /// Returns a mutable reference to the wrapped stream.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.stream
+    }
+}
+
+impl<R: tokio::io::AsyncRead + 'static>
+    TimedProcess<crate::process::Process<R>>
+{
+    /// Returns a mutable reference to the input object provided in the inner
+    /// `Process` instance's constructor.
+    pub fn input(&mut self) -> &mut R {
+        self.stream.input()
+    }
+
+    /// Requests a change to the pty's terminal size.
+    ///
+    /// This will only be applied on the next call to `poll`.
+    pub fn resize(&mut self, rows: u16, cols: u16) {
+        self.stream.resize(rows, cols);
+    }
+}
+
+impl<S: TimedStream> futures::stream::Stream for TimedProcess<S> {
+    type Item = TimedEvent;
+    type Error = crate::error::Error;
+
+    fn poll(&mut self) -> futures::Poll<Option<Self::Item>, Self::Error> {
+        let event = futures::try_ready!(self.stream.poll());
+        Ok(futures::Async::Ready(event.map(|event| {
+            TimedEvent {
+                at: self
+                    .stream
+                    .last_event_time()
+                    .unwrap_or_else(std::time::Instant::now),
+                event,
+            }
+        })))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use futures::stream::Stream as _;
+
+    #[test]
+    fn test_monotonic() {
+        let buf = std::io::Cursor::new(b"hello world\n");
+        let process = crate::process::Process::new("cat", &[], buf);
+        let before = std::time::Instant::now();
+        let events = tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(TimedProcess::new(process).collect())
+            .unwrap();
+        let after = std::time::Instant::now();
+
+        assert!(matches!(
+            events.first().unwrap().event,
+            crate::process::Event::CommandStart { .. }
+        ));
+        assert!(matches!(
+            events.last().unwrap().event,
+            crate::process::Event::CommandExit { .. }
+        ));
+        let mut prev = before;
+        for event in &events {
+            assert!(event.at >= prev);
+            prev = event.at;
+        }
+        assert!(prev <= after);
+    }
+
+    #[test]
+    fn test_resizing() {
+        let process = crate::process::test::sh("stty size");
+        let sizes =
+            futures::stream::iter_ok::<_, std::io::Error>(vec![(10, 20)]);
+        let process =
+            crate::resize::ResizingProcess::with_sizes(process, sizes);
+        let events = tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(TimedProcess::new(process).collect())
+            .unwrap();
+
+        let resize = events
+            .iter()
+            .position(|event| {
+                matches!(event.event, crate::process::Event::Resize { .. })
+            })
+            .unwrap();
+        let output = events
+            .iter()
+            .position(|event| {
+                matches!(event.event, crate::process::Event::Output { .. })
+            })
+            .unwrap();
+        assert!(events[resize].at <= events[output].at);
+
+        // the recording should take its dimensions from the resize
+        let svg = |rows, cols, events: &[TimedEvent]| {
+            let mut recorder = crate::svg::SvgRecorder::new(
+                rows,
+                cols,
+                crate::svg::SvgOptions::default(),
+            );
+            for event in events {
+                recorder.record(event);
+            }
+            recorder.to_svg().lines().next().unwrap().to_string()
+        };
+        let start: Vec<_> = events
+            .iter()
+            .filter(|event| {
+                matches!(
+                    event.event,
+                    crate::process::Event::CommandStart { .. }
+                )
+            })
+            .map(|event| TimedEvent {
+                at: event.at,
+                event: event.event.clone(),
+            })
+            .collect();
+        assert_eq!(svg(1, 1, &events), svg(10, 20, &start));
+        assert_ne!(svg(1, 1, &start), svg(10, 20, &start));
+    }
+}
@@ -0,0 +1,191 @@
+const REPLAY_CHUNK_SIZE: usize = 4 * 1024;
+
+/// The amount of output retained by a `Transcript`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TranscriptLimit {
+    /// Retain at most this many bytes of output. Older output is discarded
+    /// up to the start of a line where possible (or else up to the start of
+    /// a character), so slightly less than this may be retained.
+    Bytes(usize),
+
+    /// Retain at most this many lines of output. A trailing partial line
+    /// counts as a line.
+    Lines(usize),
+}
+
+/// A bounded buffer containing the most recent output of a process.
+///
+/// This is useful for attaching a consumer to a process after it has already
+/// been running for a while - the transcript can be replayed to the new
+/// consumer to bring it up to date before it starts receiving live output.
+///
+/// Note that only the output itself is retained, so any terminal state set
+/// by output which has since been discarded (colors, terminal modes, the
+/// window title, etc) will be missing from the replay.
+#[derive(Debug, Clone)]
+pub struct Transcript {
+    limit: TranscriptLimit,
+    data: std::collections::VecDeque<u8>,
+    newlines: usize,
+}
+
+impl Transcript {
+    /// Creates a new empty transcript with the given limit.
+    #[must_use]
+    pub fn new(limit: TranscriptLimit) -> Self {
+        Self {
+            limit,
+            data: std::collections::VecDeque::new(),
+            newlines: 0,
+        }
+    }
+
+    /// Appends output to the transcript, discarding older output as
+    /// necessary to stay within the limit.
+    #[allow(clippy::naive_bytecount)]
+    pub fn push(&mut self, data: &[u8]) {
+        self.data.extend(data.iter());
+        self.newlines += data.iter().filter(|&&c| c == b'\n').count();
+
+        match self.limit {
+            TranscriptLimit::Bytes(max) => {
+                if self.data.len() > max {
+                    let mut last = None;
+                    while self.data.len() > max {
+                        last = self.pop_front();
+                    }
+                    if last != Some(b'\n') {
+                        self.trim_partial_line();
+                    }
+                }
+            }
+            TranscriptLimit::Lines(max) => {
+                while self.lines() > max {
+                    while let Some(c) = self.pop_front() {
+                        if c == b'\n' {
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns the retained output.
+    #[must_use]
+    pub fn contents(&self) -> Vec<u8> {
+        self.data.iter().copied().collect()
+    }
+
+    /// Returns the number of bytes of output currently retained.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Returns true if no output is currently retained.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Returns the limit this transcript was created with.
+    #[must_use]
+    pub fn limit(&self) -> TranscriptLimit {
+        self.limit
+    }
+
+    /// Returns the retained output as a sequence of `Event::Output` events,
+    /// suitable for sending to a consumer before it starts receiving live
+    /// events.
+    #[must_use]
+    pub fn replay(&self) -> Vec<crate::process::Event> {
+        self.contents()
+            .chunks(REPLAY_CHUNK_SIZE)
+            .map(|chunk| crate::process::Event::Output {
+                data: chunk.to_vec(),
+            })
+            .collect()
+    }
+
+    fn lines(&self) -> usize {
+        match self.data.back() {
+            Some(b'\n') | None => self.newlines,
+            Some(_) => self.newlines + 1,
+        }
+    }
+
+    // avoids starting the replay in the middle of a line (which may also be
+    // the middle of an escape sequence), or if there is only a single line
+    // retained, in the middle of a utf8 character
+    fn trim_partial_line(&mut self) {
+        let rest_of_line =
+            self.data.iter().position(|&c| c == b'\n').map(|i| i + 1);
+        match rest_of_line {
+            Some(n) if n < self.data.len() => {
+                for _ in 0..n {
+                    self.pop_front();
+                }
+            }
+            _ => {
+                while self.data.front().is_some_and(|c| c & 0xc0 == 0x80) {
+                    self.pop_front();
+                }
+            }
+        }
+    }
+
+    fn pop_front(&mut self) -> Option<u8> {
+        let c = self.data.pop_front();
+        if c == Some(b'\n') {
+            self.newlines -= 1;
+        }
+        c
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_bytes() {
+        let mut transcript = Transcript::new(TranscriptLimit::Bytes(8));
+        assert!(transcript.is_empty());
+        transcript.push(b"hello");
+        assert_eq!(transcript.contents(), b"hello");
+        transcript.push(b" world");
+        assert_eq!(transcript.contents(), b"lo world");
+        assert_eq!(transcript.len(), 8);
+        assert_eq!(
+            transcript.replay(),
+            vec![crate::process::Event::Output {
+                data: b"lo world".to_vec()
+            }]
+        );
+    }
+
+    #[test]
+    fn test_bytes_boundaries() {
+        let mut transcript = Transcript::new(TranscriptLimit::Bytes(12));
+        transcript.push(b"\x1b[31mone\r\ntwo\r\n");
+        assert_eq!(transcript.contents(), b"two\r\n");
+
+        let mut transcript = Transcript::new(TranscriptLimit::Bytes(3));
+        transcript.push("a\u{e9}\u{e9}".as_bytes());
+        assert_eq!(transcript.contents(), "\u{e9}".as_bytes());
+    }
+
+    #[test]
+    fn test_lines() {
+        let mut transcript = Transcript::new(TranscriptLimit::Lines(2));
+        transcript.push(b"one\r\ntwo\r\n");
+        assert_eq!(transcript.contents(), b"one\r\ntwo\r\n");
+        transcript.push(b"thr");
+        assert_eq!(transcript.contents(), b"two\r\nthr");
+        transcript.push(b"ee\r\nfour\r\nfive");
+        assert_eq!(transcript.contents(), b"four\r\nfive");
+        transcript.push(b"\r\n");
+        assert_eq!(transcript.contents(), b"four\r\nfive\r\n");
+    }
+}